@@ -0,0 +1,102 @@
+use crate::document::{Command, Environment, InlineEnvironment, Item};
+
+/// Output format of the transpiler
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backend {
+    /// `\begin{name}[opts] ... \end{name}`
+    #[default]
+    Latex,
+    /// `\startname[opts] ... \stopname`
+    Context,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "latex" => Ok(Backend::Latex),
+            "context" => Ok(Backend::Context),
+            _ => Err(format!("unknown backend '{}'", s)),
+        }
+    }
+}
+
+impl Backend {
+    pub fn environment_begin(self, environment: &Environment) -> String {
+        match self {
+            Backend::Latex => environment.latex_begin(),
+            Backend::Context => environment.context_begin(),
+        }
+    }
+
+    pub fn environment_end(self, environment: &Environment) -> String {
+        match self {
+            Backend::Latex => environment.latex_end(),
+            Backend::Context => environment.context_end(),
+        }
+    }
+
+    pub fn inline_environment(self, environment: &InlineEnvironment) -> String {
+        match self {
+            Backend::Latex => environment.latex(),
+            Backend::Context => environment.context(),
+        }
+    }
+
+    /// Single-line commands and items look the same in LaTeX and ConTeXt
+    pub fn command(self, command: &Command) -> String {
+        command.latex()
+    }
+
+    pub fn item(self, item: &Item) -> String {
+        item.latex()
+    }
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    use super::Backend;
+
+    #[test]
+    fn from_str() {
+        assert_eq!("latex".parse::<Backend>(), Ok(Backend::Latex));
+        assert_eq!("context".parse::<Backend>(), Ok(Backend::Context));
+        assert_eq!(
+            "ConTeXt".parse::<Backend>(),
+            Err("unknown backend 'ConTeXt'".to_string())
+        );
+    }
+
+    #[test]
+    fn context_output() {
+        use crate::{transpile, TranspileOptions};
+
+        let input = (vec![
+            "# itemize[packed]: % foo",
+            "  * bar",
+            "  # placeformula:",
+            "    # startformula: E = mc^2",
+            "  # formula:: E = mc^2",
+        ])
+        .into_iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let expected = "\\startitemize[packed] % foo\n  \
+                        \\item bar\n  \
+                        \\startplaceformula\n    \
+                        \\startformula{E = mc^2}\n  \
+                        \\stopplaceformula\n  \
+                        \\startformula E = mc^2 \\stopformula\n\
+                        \\stopitemize\n";
+        let to = TranspileOptions {
+            prepend_do_not_edit_notice: false,
+            backend: Backend::Context,
+            ..Default::default()
+        };
+        assert_eq!(transpile(input, &to), expected);
+    }
+}
+// LCOV_EXCL_STOP
@@ -0,0 +1,249 @@
+use crate::regions::Region;
+use std::collections::BTreeMap;
+
+/// Get the indentation and the condition of a `# ?if name:` or `# ?if !name:` line
+fn if_line(line: &str) -> Option<(usize, &str)> {
+    let code = crate::utils::split_comment(line).0.trim_end();
+    let trimmed = code.trim_start_matches(' ');
+    let condition = trimmed.strip_prefix("# ?if ")?.strip_suffix(':')?.trim();
+    if condition.is_empty() {
+        None
+    } else {
+        Some((code.len() - trimmed.len(), condition))
+    }
+}
+
+fn is_else_line(line: &str, indent_depth: usize) -> bool {
+    let code = crate::utils::split_comment(line).0.trim_end();
+    code.len() > indent_depth
+        && code[..indent_depth].chars().all(|c| c == ' ')
+        && &code[indent_depth..] == "# ?else:"
+}
+
+/// Check if a line may start a conditional block
+pub fn is_conditional(line: &str) -> bool {
+    if_line(line).is_some()
+}
+
+/// Check if a condition holds, i.e. if the name is defined and not `false` or `0`
+pub fn holds(condition: &str, defines: &BTreeMap<String, String>) -> bool {
+    let (negated, name) = match condition.strip_prefix('!') {
+        Some(name) => (true, name.trim_start()),
+        None => (false, condition),
+    };
+    let defined = defines.get(name).is_some_and(|v| v != "false" && v != "0");
+
+    defined != negated
+}
+
+/// Source line index, region and text of a line
+type Line = (usize, Region, String);
+
+/// Get the end of a body which is indented deeper than `indent_depth`, starting at `begin`
+fn body_end(lines: &[Line], begin: usize, indent_depth: usize) -> usize {
+    let mut end = begin;
+    for (i, (_, region, l)) in lines.iter().enumerate().skip(begin) {
+        if l.trim().is_empty() {
+            continue;
+        }
+        // Passthrough lines are not closed by their indentation, just like in `parse_lines`
+        if *region == Region::Transpiled && l.len() - l.trim_start().len() <= indent_depth {
+            break;
+        }
+        // Trailing blank lines do not belong to the body
+        end = i + 1;
+    }
+    end
+}
+
+fn select_into(lines: &[Line], defines: &BTreeMap<String, String>, selected: &mut Vec<Line>) {
+    let is_transpiled = |j: usize| lines[j].1 == Region::Transpiled;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let (indent_depth, condition) = match if_line(&lines[i].2).filter(|_| is_transpiled(i)) {
+            Some(c) => c,
+            None => {
+                selected.push(lines[i].clone());
+                i += 1;
+                continue;
+            }
+        };
+
+        let if_body = (i + 1, body_end(lines, i + 1, indent_depth));
+        // Blank lines before an else are part of the if body
+        let next = (if_body.1..lines.len())
+            .find(|&j| !lines[j].2.trim().is_empty())
+            .filter(|&j| is_transpiled(j) && is_else_line(&lines[j].2, indent_depth));
+        let (if_body, else_body) = match next {
+            Some(j) => (
+                (if_body.0, j),
+                Some((j + 1, body_end(lines, j + 1, indent_depth))),
+            ),
+            None => (if_body, None),
+        };
+        i = else_body.map_or(if_body.1, |(_, end)| end);
+
+        let branch = if holds(condition, defines) {
+            Some(if_body)
+        } else {
+            else_body
+        };
+        if let Some((begin, end)) = branch {
+            // The selected body takes the place of the condition, passthrough lines may be
+            // indented less than the body and are shifted as far as possible
+            let body = &lines[begin..end];
+            let body_indent = body
+                .iter()
+                .filter(|(_, r, l)| *r == Region::Transpiled && !l.trim().is_empty())
+                .map(|(_, _, l)| l.len() - l.trim_start().len())
+                .min()
+                .unwrap_or(indent_depth);
+            let dedent = body_indent - indent_depth;
+            let body = body
+                .iter()
+                .map(|(o, r, l)| {
+                    let spaces = l.len() - l.trim_start_matches(' ').len();
+                    (*o, *r, l[spaces.min(dedent)..].to_string())
+                })
+                .collect::<Vec<_>>();
+            select_into(&body, defines, selected);
+        }
+    }
+}
+
+/// Drop the unselected branches of all conditional blocks, together with the index of the
+/// source line of every remaining line
+///
+/// Conditionals in verbatim bodies and where transpilation is switched off are kept as they are.
+pub(crate) fn select(
+    lines: Vec<String>,
+    origins: Vec<usize>,
+    regions: &[Region],
+    defines: &BTreeMap<String, String>,
+) -> (Vec<String>, Vec<usize>) {
+    let lines = origins
+        .into_iter()
+        .zip(regions.iter().copied())
+        .zip(lines)
+        .map(|((o, r), l)| (o, r, l))
+        .collect::<Vec<_>>();
+    let mut selected = Vec::with_capacity(lines.len());
+    select_into(&lines, defines, &mut selected);
+
+    selected.into_iter().map(|(o, _, l)| (l, o)).unzip()
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    fn defines(d: &[(&str, &str)]) -> BTreeMap<String, String> {
+        d.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn if_line() {
+        use super::if_line;
+
+        assert_eq!(if_line("# ?if draft:"), Some((0, "draft")));
+        assert_eq!(if_line("  # ?if !draft: % foo"), Some((2, "!draft")));
+        assert_eq!(if_line("# ?if :"), None);
+        assert_eq!(if_line("# ?if draft"), None);
+        assert_eq!(if_line("#?if draft:"), None);
+        assert_eq!(if_line("# ?else:"), None);
+    }
+
+    #[test]
+    fn holds() {
+        use super::holds;
+
+        let d = defines(&[("draft", "true"), ("review", "false"), ("final", "0")]);
+        assert!(holds("draft", &d));
+        assert!(!holds("!draft", &d));
+        assert!(!holds("review", &d));
+        assert!(!holds("final", &d));
+        assert!(!holds("foo", &d));
+        assert!(holds("! foo", &d));
+    }
+
+    #[test]
+    fn select() {
+        use super::select;
+        use crate::regions::scan;
+
+        let input = [
+            "a",
+            "# ?if draft:",
+            "  b",
+            "    c",
+            "",
+            "# ?else:",
+            "  d",
+            "",
+            "e",
+            "  # ?if !draft: % foo",
+            "    f",
+            "  # ?if review:",
+            "        g",
+            "  # ?else:",
+            "",
+            "h",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+        let origins = (0..input.len()).collect::<Vec<_>>();
+        let regions = scan(&input, &Default::default());
+
+        let (lines, origins_draft) = select(
+            input.clone(),
+            origins.clone(),
+            &regions,
+            &defines(&[("draft", "")]),
+        );
+        assert_eq!(lines, ["a", "b", "  c", "", "", "e", "", "h"]);
+        assert_eq!(origins_draft, [0, 2, 3, 4, 7, 8, 14, 15]);
+
+        let (lines, _) = select(input, origins, &regions, &defines(&[("review", "1")]));
+        assert_eq!(lines, ["a", "d", "", "e", "  f", "  g", "", "h"]);
+
+        // Verbatim bodies and regions which are not transpiled belong to the body, but their
+        // conditionals are kept
+        let input = [
+            "# ?if draft:",
+            "  # lstlisting:",
+            "    # ?if debug:",
+            "      x",
+            "  % indentex: off",
+            "# ?if debug:",
+            "% indentex: on",
+            "  \\",
+            "y",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+        let origins = (0..input.len()).collect::<Vec<_>>();
+        let regions = scan(&input, &Default::default());
+
+        let (lines, _) = select(input, origins, &regions, &defines(&[("draft", "")]));
+        assert_eq!(
+            lines,
+            [
+                "# lstlisting:",
+                "  # ?if debug:",
+                "    x",
+                "% indentex: off",
+                "# ?if debug:",
+                "% indentex: on",
+                "\\",
+                "y",
+            ]
+        );
+    }
+}
+// LCOV_EXCL_STOP
@@ -0,0 +1,235 @@
+use crate::ItemMarker;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiagnosticCode {
+    /// A hashline is indented with tabs and is thus not recognised
+    TabBeforeHashline,
+    /// The hash sign is not followed by a whitespace, e.g. `#foo: bar`
+    MissingSpaceAfterHash,
+    /// A line starts with `# ` but has no name or no colon
+    MalformedHashline,
+    /// An itemline outside of a list-like environment
+    ItemOutsideList,
+    /// A `{{name}}` placeholder without a value
+    UndefinedVariable,
+    /// A front-matter line which is neither an entry nor a list item
+    MalformedFrontMatter,
+}
+
+/// A single finding in the indentex source
+///
+/// Line and columns are zero-based; columns are counted in chars and the range is half-open.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: usize,
+    pub columns: Range<usize>,
+    pub code: DiagnosticCode,
+    pub message: String,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+impl DiagnosticCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiagnosticCode::TabBeforeHashline => "tab-before-hashline",
+            DiagnosticCode::MissingSpaceAfterHash => "missing-space-after-hash",
+            DiagnosticCode::MalformedHashline => "malformed-hashline",
+            DiagnosticCode::ItemOutsideList => "item-outside-list",
+            DiagnosticCode::UndefinedVariable => "undefined-variable",
+            DiagnosticCode::MalformedFrontMatter => "malformed-front-matter",
+        }
+    }
+
+    pub fn severity(self) -> Severity {
+        match self {
+            DiagnosticCode::ItemOutsideList | DiagnosticCode::UndefinedVariable => {
+                Severity::Warning
+            }
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl Diagnostic {
+    pub(crate) fn new(
+        code: DiagnosticCode,
+        line: usize,
+        columns: Range<usize>,
+        message: &str,
+    ) -> Self {
+        Self {
+            severity: code.severity(),
+            line,
+            columns,
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}[{}]: {}",
+            self.line + 1,
+            self.columns.start + 1,
+            self.severity.as_str(),
+            self.code.as_str(),
+            self.message,
+        )
+    }
+}
+
+/// Check a single source line for input that looks like indentex but will not be transpiled
+pub fn check_line(
+    line: &str,
+    line_num: usize,
+    list_like_active: bool,
+    item_markers: &[ItemMarker],
+) -> Option<Diagnostic> {
+    use crate::parsers::{hashline_parser, itemline_parser};
+
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    let indent_chars = line[..indent].chars().count();
+    let line_chars = line.chars().count();
+
+    if let Some(after_hash) = trimmed.strip_prefix('#') {
+        if hashline_parser(line).is_ok() {
+            return None;
+        }
+
+        if line[..indent].contains('\t') && hashline_parser(trimmed).is_ok() {
+            Some(Diagnostic::new(
+                DiagnosticCode::TabBeforeHashline,
+                line_num,
+                0..indent_chars,
+                "hashline is indented with tabs and will not be transpiled",
+            ))
+        } else if after_hash.starts_with(' ') {
+            Some(Diagnostic::new(
+                DiagnosticCode::MalformedHashline,
+                line_num,
+                indent_chars..line_chars,
+                "hashline has no name or no colon and will not be transpiled",
+            ))
+        } else if hashline_parser(&format!("# {}", after_hash)).is_ok() {
+            Some(Diagnostic::new(
+                DiagnosticCode::MissingSpaceAfterHash,
+                line_num,
+                indent_chars..indent_chars + 1,
+                "hash sign must be followed by a space",
+            ))
+        } else {
+            // Probably a macro parameter, e.g. `#1`
+            None
+        }
+    } else if !list_like_active && itemline_parser(line, item_markers).is_ok() {
+        // Markers are a single character, numbered ones are preceded by digits
+        let digits = trimmed.len()
+            - trimmed
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        Some(Diagnostic::new(
+            DiagnosticCode::ItemOutsideList,
+            line_num,
+            indent_chars..indent_chars + digits + 1,
+            "itemline outside of a list-like environment is not transpiled",
+        ))
+    } else {
+        None
+    }
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    use super::{check_line, DiagnosticCode, Severity};
+    use crate::ItemMarker;
+
+    #[test]
+    fn valid_lines() {
+        for (line, list_like_active) in [
+            ("", false),
+            ("foo bar", false),
+            ("# foo: bar", false),
+            ("  # foo[qux]:", false),
+            ("  * foo", true),
+            (r"\#", false),
+            (r"#1}", false),
+            ("  #2", false),
+            ("- foo", false),
+            ("1. foo", false),
+        ] {
+            assert_eq!(
+                check_line(line, 0, list_like_active, &[ItemMarker::Asterisk]),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_lines() {
+        for (line, code, columns) in [
+            ("\t# foo: bar", DiagnosticCode::TabBeforeHashline, 0..1),
+            (" \t # foo:", DiagnosticCode::TabBeforeHashline, 0..3),
+            ("#foo: bar", DiagnosticCode::MissingSpaceAfterHash, 0..1),
+            ("  #foo:", DiagnosticCode::MissingSpaceAfterHash, 2..3),
+            ("# foo bar", DiagnosticCode::MalformedHashline, 0..9),
+            ("  #  foo:", DiagnosticCode::MalformedHashline, 2..9),
+            ("  # äü", DiagnosticCode::MalformedHashline, 2..6),
+            (" * foo", DiagnosticCode::ItemOutsideList, 1..2),
+            ("  - foo", DiagnosticCode::ItemOutsideList, 2..3),
+            ("10) foo", DiagnosticCode::ItemOutsideList, 0..3),
+        ] {
+            let markers = [ItemMarker::Asterisk, ItemMarker::Dash, ItemMarker::Numbered];
+            let d = check_line(line, 42, false, &markers).unwrap();
+            assert_eq!(d.code, code);
+            assert_eq!(d.line, 42);
+            assert_eq!(d.columns, columns);
+        }
+    }
+
+    #[test]
+    fn severities() {
+        assert_eq!(
+            DiagnosticCode::MalformedHashline.severity(),
+            Severity::Error
+        );
+        assert_eq!(
+            DiagnosticCode::ItemOutsideList.severity(),
+            Severity::Warning
+        );
+        assert_eq!(
+            DiagnosticCode::UndefinedVariable.severity(),
+            Severity::Warning
+        );
+    }
+
+    #[test]
+    fn display() {
+        let d = check_line("  #foo: bar", 3, false, &[]).unwrap();
+        assert_eq!(
+            format!("{}", d),
+            "4:3: error[missing-space-after-hash]: hash sign must be followed by a space"
+        );
+    }
+}
+// LCOV_EXCL_STOP
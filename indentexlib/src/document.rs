@@ -0,0 +1,126 @@
+use std::ops::Range;
+
+pub use crate::parsing_types::{Command, Environment, InlineEnvironment, Item, TableRules};
+
+/// An indentex document as a tree of nodes nested by indentation
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    pub nodes: Vec<Node>,
+}
+
+/// A node together with the zero-based, half-open range of source lines it was parsed from
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node {
+    pub kind: NodeKind,
+    pub span: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum NodeKind {
+    /// Environment opened by a hashline, e.g. `# equation:`, with its indented body
+    Environment {
+        environment: Environment,
+        children: Vec<Node>,
+    },
+    /// Environment opened and closed on the same line, e.g. `# equation:: E = mc^2`
+    InlineEnvironment(InlineEnvironment),
+    /// Single-line command, e.g. `# section: Foo`
+    Command(Command),
+    /// Itemline in a list-like environment, e.g. `* foo`
+    Item(Item),
+    /// Line which is passed through unchanged
+    Text(String),
+}
+
+impl Node {
+    pub fn new(kind: NodeKind, span: Range<usize>) -> Self {
+        Self { kind, span }
+    }
+}
+
+/// Map spans of nodes parsed from expanded lines back to the source lines
+pub(crate) fn remap_spans(nodes: &mut [Node], origins: &[usize]) {
+    for node in nodes.iter_mut() {
+        let start = origins.get(node.span.start).cloned();
+        let last = node.span.end.checked_sub(1).and_then(|l| origins.get(l));
+        if let (Some(start), Some(last)) = (start, last) {
+            node.span = start..last + 1;
+        }
+        if let NodeKind::Environment { children, .. } = &mut node.kind {
+            remap_spans(children, origins);
+        }
+    }
+}
+
+// LCOV_EXCL_START
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    #[test]
+    fn json_roundtrip() {
+        use super::Document;
+
+        let lines = (vec![
+            "# itemize[noitemsep]: % foo",
+            "  * bar",
+            "# baz: qux",
+            "quux",
+        ])
+        .into_iter()
+        .map(|s| s.to_string())
+        .collect();
+        let document = crate::parse(lines, &Default::default());
+
+        let json = serde_json::to_value(&document).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "nodes": [
+                    {
+                        "kind": {
+                            "environment": {
+                                "environment": {
+                                    "indent_depth": 0,
+                                    "name": "itemize",
+                                    "opts": "[noitemsep]",
+                                    "comment": "% foo",
+                                    "is_list_like": true,
+                                },
+                                "children": [
+                                    {
+                                        "kind": {"item": {"indent_depth": 2, "command": "item", "number": null, "label": null, "content": "bar"}},
+                                        "span": {"start": 1, "end": 2},
+                                    },
+                                ],
+                            },
+                        },
+                        "span": {"start": 0, "end": 2},
+                    },
+                    {
+                        "kind": {
+                            "command": {
+                                "indent_depth": 0,
+                                "name": "baz",
+                                "opts": "",
+                                "args": ["qux"],
+                                "comment": "",
+                            },
+                        },
+                        "span": {"start": 2, "end": 3},
+                    },
+                    {
+                        "kind": {"text": "quux"},
+                        "span": {"start": 3, "end": 4},
+                    },
+                ],
+            })
+        );
+
+        let deserialized: Document = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized, document);
+    }
+}
+// LCOV_EXCL_STOP
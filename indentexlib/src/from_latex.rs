@@ -0,0 +1,456 @@
+use crate::parsers::split_args;
+use crate::parsing_types::is_a_list_environment;
+use crate::{DEFAULT_VERBATIM_ENVIRONMENTS, LINESEP};
+
+const INDENT_WIDTH: usize = 2;
+
+enum Token<'a> {
+    Begin(&'a str),
+    End(&'a str),
+}
+
+/// A LaTeX line split into code and comment, with all environment delimiters in the code
+struct ScannedLine<'a> {
+    code: &'a str,
+    comment: &'a str,
+    tokens: Vec<Token<'a>>,
+}
+
+enum Role {
+    /// Opens an environment, contains the hashline without indentation
+    Begin(String, bool),
+    /// Closes an environment which has been converted into a hashline
+    End,
+    /// Line inside a verbatim environment
+    Verbatim,
+    Other,
+}
+
+fn scan_line(line: &str) -> ScannedLine<'_> {
+    let mut tokens = Vec::new();
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '%' => {
+                return ScannedLine {
+                    code: &line[..i],
+                    comment: &line[i..],
+                    tokens,
+                }
+            }
+            '\\' => {
+                let start = i + 1;
+                let mut end = start;
+                while let Some(&(j, c)) = chars.peek() {
+                    if !c.is_ascii_alphabetic() {
+                        break;
+                    }
+                    end = j + 1;
+                    chars.next();
+                }
+                if end == start {
+                    // Control symbol such as `\%` or `\\`
+                    chars.next();
+                    continue;
+                }
+                let name = line[end..]
+                    .strip_prefix('{')
+                    .and_then(|r| r.split_once('}'))
+                    .map(|(name, _)| name);
+                match (&line[start..end], name) {
+                    ("begin", Some(name)) => tokens.push(Token::Begin(name)),
+                    ("end", Some(name)) => tokens.push(Token::End(name)),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    ScannedLine {
+        code: line,
+        comment: "",
+        tokens,
+    }
+}
+
+/// Split off a balanced `[...]` or `{...}` group from the beginning of a string
+fn split_group(s: &str) -> Option<(&str, &str)> {
+    let close = match s.chars().next()? {
+        '[' => ']',
+        '{' => '}',
+        _ => return None,
+    };
+    let mut depth = 0_usize;
+    let mut escaped = false;
+
+    for (i, c) in s.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            c if c == close && depth == 0 => return Some(s.split_at(i + 1)),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+#[inline]
+fn escape_colons(s: &str) -> String {
+    s.replace(':', r"\:")
+}
+
+#[inline]
+fn with_comment(hashline: String, comment: &str) -> String {
+    if comment.is_empty() {
+        hashline
+    } else {
+        format!("{} {}", hashline, comment.trim())
+    }
+}
+
+fn environment_hashline(line: &ScannedLine, name: &str) -> Option<String> {
+    if name.is_empty() || name.contains(|c| "\\%([{ \t".contains(c)) {
+        return None;
+    }
+
+    let opts = line
+        .code
+        .trim()
+        .strip_prefix(r"\begin{")?
+        .strip_prefix(name)?
+        .strip_prefix('}')?
+        .trim_start();
+    // Anything except options after `\begin{...}` would be swallowed into the options
+    let mut rest = opts;
+    while !rest.is_empty() {
+        rest = split_group(rest)?.1;
+    }
+
+    let hashline = format!("# {}{}:", escape_colons(name), escape_colons(opts));
+    Some(with_comment(hashline, line.comment))
+}
+
+fn command_hashline(line: &ScannedLine) -> Option<String> {
+    let code = line.code.trim().strip_prefix('\\')?;
+    let mut name_len = code
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(code.len());
+    if code[name_len..].starts_with('*') {
+        name_len += 1;
+    }
+    let (name, mut rest) = code.split_at(name_len);
+    if name.is_empty() || ["begin", "end", "item"].contains(&name) {
+        return None;
+    }
+
+    let mut opts_len = 0;
+    while rest.starts_with('[') {
+        let (opt, r) = split_group(rest)?;
+        opts_len += opt.len();
+        rest = r;
+    }
+    let opts = &code[name_len..name_len + opts_len];
+
+    let mut args = Vec::new();
+    while !rest.is_empty() {
+        let (arg, r) = split_group(rest)?;
+        let arg = arg.strip_prefix('{')?.strip_suffix('}')?;
+        // Whitespaces around args would be lost and there is no way to escape a separator
+        if arg != arg.trim() || split_args(arg).len() > 1 {
+            return None;
+        }
+        args.push(arg.to_string());
+        rest = r;
+    }
+    // Empty last arg would open an environment
+    if args.last().is_none_or(|a| a.is_empty()) {
+        return None;
+    }
+
+    let hashline = format!("# {}{}: {}", name, escape_colons(opts), args.join(" | "));
+    Some(with_comment(hashline, line.comment))
+}
+
+fn itemline(line: &str) -> Option<String> {
+    let content = line.trim_start().strip_prefix(r"\item")?;
+    if content.is_empty() {
+        Some("*".to_string())
+    } else if content.starts_with(|c: char| c.is_whitespace() || c == '[') {
+        Some(format!("* {}", content.trim()))
+    } else {
+        None
+    }
+}
+
+/// Find environments which can be converted into hashlines: both delimiters must be alone on
+/// their lines and properly nested
+fn assign_roles(lines: &[String], scanned: &[ScannedLine]) -> Vec<Role> {
+    let mut roles = lines.iter().map(|_| Role::Other).collect::<Vec<_>>();
+    let mut stack: Vec<(&str, usize)> = Vec::new();
+    let mut verbatim: Option<String> = None;
+
+    for (line_num, (line, scanned_line)) in lines.iter().zip(scanned).enumerate() {
+        if let Some(end) = verbatim.as_ref() {
+            if line.contains(end.as_str()) {
+                verbatim = None;
+            } else {
+                roles[line_num] = Role::Verbatim;
+            }
+            continue;
+        }
+
+        for token in scanned_line.tokens.iter() {
+            match *token {
+                Token::Begin(name) if DEFAULT_VERBATIM_ENVIRONMENTS.contains(&name) => {
+                    let end = format!(r"\end{{{}}}", name);
+                    if !line.contains(&end) {
+                        verbatim = Some(end);
+                    }
+                    break;
+                }
+                Token::Begin(name) => stack.push((name, line_num)),
+                Token::End(name) => {
+                    let pos = match stack.iter().rposition(|&(n, _)| n == name) {
+                        Some(pos) => pos,
+                        None => continue,
+                    };
+                    let begin_num = stack[pos].1;
+                    stack.truncate(pos);
+
+                    let alone = |l: &ScannedLine| l.tokens.len() == 1;
+                    if begin_num == line_num || !alone(&scanned[begin_num]) || !alone(scanned_line)
+                    {
+                        continue;
+                    }
+                    if !scanned_line.comment.is_empty()
+                        || scanned_line.code.trim() != format!(r"\end{{{}}}", name)
+                    {
+                        continue;
+                    }
+                    if let Some(hashline) = environment_hashline(&scanned[begin_num], name) {
+                        roles[begin_num] = Role::Begin(hashline, is_a_list_environment(name));
+                        roles[line_num] = Role::End;
+                    }
+                }
+            }
+        }
+    }
+
+    roles
+}
+
+#[inline]
+fn push_line(converted: &mut String, depth: usize, line: &str) {
+    if !line.is_empty() {
+        converted.push_str(&" ".repeat(depth * INDENT_WIDTH));
+        converted.push_str(line);
+    }
+    converted.push_str(LINESEP);
+}
+
+pub fn convert(lines: &[String]) -> String {
+    let scanned = lines.iter().map(|l| scan_line(l)).collect::<Vec<_>>();
+    let roles = assign_roles(lines, &scanned);
+
+    let input_size = lines.iter().fold(0, |sum, l| sum + l.len()) + lines.len();
+    let mut converted = String::with_capacity(input_size);
+    // List-likeness of all open environments
+    let mut env_stack: Vec<bool> = Vec::new();
+
+    for ((line, scanned_line), role) in lines.iter().zip(scanned.iter()).zip(roles) {
+        let depth = env_stack.len();
+        match role {
+            Role::Begin(hashline, is_list_like) => {
+                push_line(&mut converted, depth, &hashline);
+                env_stack.push(is_list_like);
+            }
+            Role::End => {
+                env_stack.pop();
+            }
+            // Verbatim bodies are passed through by `transpile`, so they must not be indented
+            Role::Verbatim => push_line(&mut converted, 0, line),
+            Role::Other => {
+                let hashline = if scanned_line.tokens.is_empty() {
+                    command_hashline(scanned_line)
+                } else {
+                    None
+                };
+                let itemline = match env_stack.last() {
+                    Some(true) => itemline(line),
+                    _ => None,
+                };
+                let new_line = hashline.or(itemline);
+                push_line(
+                    &mut converted,
+                    depth,
+                    new_line.as_deref().unwrap_or_else(|| line.trim_start()),
+                );
+            }
+        }
+    }
+
+    converted
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn split_group() {
+        use super::split_group;
+
+        assert_eq!(split_group("[a]b"), Some(("[a]", "b")));
+        assert_eq!(split_group("{a{b}c}d"), Some(("{a{b}c}", "d")));
+        assert_eq!(split_group("[a={]}]]"), Some(("[a={]}]", "]")));
+        assert_eq!(split_group(r"{a\}}"), Some((r"{a\}}", "")));
+        assert_eq!(split_group("{a"), None);
+        assert_eq!(split_group("a"), None);
+        assert_eq!(split_group(""), None);
+    }
+
+    #[test]
+    fn environments_commands_and_items() {
+        use super::convert;
+
+        let latex = lines(
+            "\\documentclass[a4paper]{article}\n\
+             \\begin{document}\n\
+             \\section*{Intro} % first\n\
+             \\href{https://x.org}{$|x|$}\n\
+             \\begin{figure}[htbp]\n\
+             \\includegraphics[width=5cm]{foo.pdf}\n\
+             \\end{figure}\n\
+             \n\
+             \\begin{itemize}[label=\\textbullet:]\n\
+               \\item foo \\% bar\n\
+                 baz\n\
+               \\item[qux]\n\
+               \\item\n\
+             \\end{itemize}\n\
+             \\item not in a list\n\
+             \\end{document}",
+        );
+        let expected = "# documentclass[a4paper]: article\n\
+                        # document:\n  \
+                        # section*: Intro % first\n  \
+                        # href: https://x.org | $|x|$\n  \
+                        # figure[htbp]:\n    \
+                        # includegraphics[width=5cm]: foo.pdf\n\
+                        \n  \
+                        # itemize[label=\\textbullet\\:]:\n    \
+                        * foo \\% bar\n    \
+                        baz\n    \
+                        * [qux]\n    \
+                        *\n  \
+                        \\item not in a list\n";
+        assert_eq!(convert(&latex), expected);
+    }
+
+    #[test]
+    fn unconvertible_lines() {
+        use super::convert;
+
+        let latex = lines(
+            "\\begin{center} centered\n\
+             \\end{center}\n\
+             \\begin{equation} x \\end{equation}\n\
+             \\begin{foo}\n\
+             \\end{foo} % bar\n\
+             \\begin{unclosed}\n\
+             \\emph{ a }\n\
+             \\frac{a}{}\n\
+             \\left\\|{a}\n\
+             \\foo{a | b}\n\
+             \\hfill{}\n\
+             \\\\{a}\n\
+             \\foo{a} b",
+        );
+        let expected = latex.iter().map(|l| format!("{}\n", l)).collect::<String>();
+        assert_eq!(convert(&latex), expected);
+    }
+
+    #[test]
+    fn nested_and_verbatim_environments() {
+        use super::convert;
+
+        let latex = lines(
+            "\\begin{a}\n\
+             \\begin{b}\\begin{c}\n\
+             \\end{c}\n\
+             \\end{b}\n\
+             \\begin{verbatim}\n\
+             \\begin{a}\n\
+             \x20 \\section{foo}\n\
+             \\end{verbatim}\n\
+             \\end{a}",
+        );
+        let expected = "# a:\n  \
+                        \\begin{b}\\begin{c}\n  \
+                        \\end{c}\n  \
+                        \\end{b}\n  \
+                        \\begin{verbatim}\n\
+                        \\begin{a}\n  \
+                        \\section{foo}\n  \
+                        \\end{verbatim}\n";
+        assert_eq!(convert(&latex), expected);
+    }
+
+    #[test]
+    fn roundtrip() {
+        use crate::{transpile, TranspileOptions};
+
+        let latex = lines(
+            "\\begin{document}\n\
+             \\frac{|a|}{b}\n\
+             \\begin{tabular}{c:c} % table\n\
+             a & b\n\
+             \\end{tabular}\n\
+             \\begin{verbatim}\n\
+             def f():\n\
+             \x20   return 1\n\
+             \\end{verbatim}\n\
+             \\begin{enumerate}\n\
+             \\item \\textbf{foo}\n\
+             \\begin{itemize}\n\
+             \\item bar\n\
+             \\end{itemize}\n\
+             \\end{enumerate}\n\
+             \\end{document}",
+        );
+        let expected = "\\begin{document}\n  \
+                        \\frac{|a|}{b}\n  \
+                        \\begin{tabular}{c:c} % table\n    \
+                        a & b\n  \
+                        \\end{tabular}\n  \
+                        \\begin{verbatim}\n\
+                        def f():\n    \
+                        return 1\n  \
+                        \\end{verbatim}\n  \
+                        \\begin{enumerate}\n    \
+                        \\item \\textbf{foo}\n    \
+                        \\begin{itemize}\n      \
+                        \\item bar\n    \
+                        \\end{itemize}\n  \
+                        \\end{enumerate}\n\
+                        \\end{document}\n";
+        let options = TranspileOptions {
+            prepend_do_not_edit_notice: false,
+            ..Default::default()
+        };
+        let indentex = lines(&super::convert(&latex));
+        assert_eq!(transpile(indentex, &options), expected);
+    }
+}
+// LCOV_EXCL_STOP
@@ -0,0 +1,460 @@
+use crate::diagnostics::{Diagnostic, DiagnosticCode};
+use std::collections::BTreeMap;
+
+/// Delimiter of a TOML front-matter
+pub const TOML_DELIMITER: &str = "+++";
+/// Delimiter of a YAML front-matter
+pub const YAML_DELIMITER: &str = "---";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Format {
+    Toml,
+    Yaml,
+}
+
+/// Value of a front-matter key; booleans and numbers are kept as strings
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum Value {
+    String(String),
+    List(Vec<String>),
+}
+
+/// Metadata block at the very top of an indentex file, e.g.
+///
+/// ```text
+/// +++
+/// documentclass = "article"
+/// classoptions = ["a4paper", "11pt"]
+/// packages = ["amsmath", "[utf8]{inputenc}"]
+/// title = "Report"
+/// author = ["Jane Doe", "John Doe"]
+/// +++
+/// ```
+///
+/// A YAML front-matter is delimited by `---` and uses `key: value`; lists are written as
+/// `[a, b]` or as `- a` lines below the key. Only this flat subset of TOML and YAML is supported.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrontMatter {
+    pub format: Format,
+    pub entries: BTreeMap<String, Value>,
+}
+
+impl Value {
+    /// Get a single string or all list items
+    pub fn items(&self) -> Vec<&str> {
+        match *self {
+            Value::String(ref s) => vec![s.as_str()],
+            Value::List(ref l) => l.iter().map(|s| s.as_str()).collect(),
+        }
+    }
+}
+
+impl FrontMatter {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.get(key)
+    }
+
+    /// Generate the preamble from the keys `documentclass`, `classoptions`, `packages`,
+    /// `title`, `author` and `date`
+    ///
+    /// Packages with options are given as `[options]{name}`, several authors are joined with
+    /// `\and`.
+    pub fn preamble(&self) -> Vec<String> {
+        let joined = |key: &str, sep: &str| self.get(key).map(|v| v.items().join(sep));
+        let mut preamble = Vec::new();
+
+        if let Some(class) = joined("documentclass", "") {
+            match joined("classoptions", ",") {
+                Some(opts) if !opts.is_empty() => {
+                    preamble.push(format!(r"\documentclass[{}]{{{}}}", opts, class))
+                }
+                _ => preamble.push(format!(r"\documentclass{{{}}}", class)),
+            }
+        }
+        if let Some(packages) = self.get("packages") {
+            for p in packages.items() {
+                if p.starts_with('[') {
+                    preamble.push(format!(r"\usepackage{}", p));
+                } else {
+                    preamble.push(format!(r"\usepackage{{{}}}", p));
+                }
+            }
+        }
+        for (key, sep) in [("title", " "), ("author", r" \and "), ("date", " ")] {
+            if let Some(value) = joined(key, sep) {
+                preamble.push(format!(r"\{}{{{}}}", key, value));
+            }
+        }
+
+        preamble
+    }
+
+    /// Get all string values, e.g. for `{{name}}` variables
+    pub fn variables(&self) -> BTreeMap<String, String> {
+        self.entries
+            .iter()
+            .filter_map(|(k, v)| match *v {
+                Value::String(ref s) => Some((k.clone(), s.clone())),
+                Value::List(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Parse a quoted or bare scalar, the rest after it must be empty or a comment
+fn scalar(input: &str) -> Option<String> {
+    let (value, rest) = scalar_prefix(input.trim(), &['#'])?;
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Parse a quoted or bare scalar at the start of the input, bare ones end before a terminator
+fn scalar_prefix<'a>(input: &'a str, terminators: &[char]) -> Option<(String, &'a str)> {
+    let mut chars = input.char_indices();
+    match chars.next()?.1 {
+        '"' => {
+            let mut value = String::new();
+            let mut escaped = false;
+            for (i, c) in chars {
+                match (escaped, c) {
+                    (false, '\\') => escaped = true,
+                    (false, '"') => return Some((value, &input[i + 1..])),
+                    (false, c) => value.push(c),
+                    (true, c) => {
+                        value.push(match c {
+                            'n' => '\n',
+                            't' => '\t',
+                            c => c,
+                        });
+                        escaped = false;
+                    }
+                }
+            }
+            None
+        }
+        '\'' => {
+            let end = input[1..].find('\'')? + 1;
+            Some((input[1..end].to_string(), &input[end + 1..]))
+        }
+        _ => {
+            // Comments must be separated by a whitespace
+            let end = input
+                .char_indices()
+                .find(|&(i, c)| {
+                    terminators.contains(&c)
+                        && (c != '#' || input[..i].ends_with(char::is_whitespace))
+                })
+                .map_or(input.len(), |(i, _)| i);
+            let value = input[..end].trim();
+            if value.is_empty() {
+                None
+            } else {
+                Some((value.to_string(), &input[end..]))
+            }
+        }
+    }
+}
+
+/// Parse a list like `["a", b]`, the rest after it must be empty or a comment
+fn list(input: &str) -> Option<Vec<String>> {
+    let mut rest = input.trim().strip_prefix('[')?.trim_start();
+    let mut items = Vec::new();
+    loop {
+        if let Some(r) = rest.strip_prefix(']') {
+            rest = r;
+            break;
+        }
+        let (item, r) = scalar_prefix(rest, &[',', ']'])?;
+        items.push(item);
+        rest = r.trim_start();
+        if let Some(r) = rest.strip_prefix(',') {
+            rest = r.trim_start();
+        } else if !rest.starts_with(']') {
+            return None;
+        }
+    }
+
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Some(items)
+    } else {
+        None
+    }
+}
+
+fn value(input: &str) -> Option<Value> {
+    if input.trim_start().starts_with('[') {
+        list(input).map(Value::List)
+    } else {
+        scalar(input).map(Value::String)
+    }
+}
+
+fn is_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Split an entry into its key and the unparsed value
+fn entry(line: &str, format: Format) -> Option<(&str, &str)> {
+    let (key, value) = match format {
+        Format::Toml => line.split_once('=')?,
+        Format::Yaml => match line.split_once(": ") {
+            Some(e) => e,
+            None => (line.trim_end().strip_suffix(':')?, ""),
+        },
+    };
+    let key = key.trim();
+    if is_key(key) {
+        Some((key, value))
+    } else {
+        None
+    }
+}
+
+/// Parse the front-matter at the top of the source lines
+///
+/// Returns the front-matter, the number of lines it spans including both delimiters and the
+/// diagnostics for lines which could not be parsed. Without a closing delimiter, there is no
+/// front-matter.
+pub fn parse(lines: &[String]) -> Option<(FrontMatter, usize, Vec<Diagnostic>)> {
+    let first = lines.first()?.trim_end();
+    let format = match first {
+        TOML_DELIMITER => Format::Toml,
+        YAML_DELIMITER => Format::Yaml,
+        _ => return None,
+    };
+    let end = 1 + lines[1..].iter().position(|l| l.trim_end() == first)?;
+
+    let mut entries = BTreeMap::new();
+    let mut diagnostics = Vec::new();
+    // TOML lists may span multiple lines, YAML lists consist of `- item` lines
+    let mut pending: Option<(String, String, usize)> = None;
+    let mut yaml_list: Option<(String, Vec<String>)> = None;
+    for (i, line) in lines.iter().enumerate().take(end).skip(1) {
+        let trimmed = line.trim();
+        if let Some((key, mut unparsed, begin)) = pending.take() {
+            unparsed.push(' ');
+            unparsed.push_str(trimmed);
+            match list(&unparsed) {
+                Some(l) => {
+                    entries.insert(key, Value::List(l));
+                }
+                None if i + 1 < end => pending = Some((key, unparsed, begin)),
+                None => diagnostics.push(malformed(begin, &lines[begin])),
+            }
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .filter(|_| format == Format::Yaml)
+        {
+            match (yaml_list.as_mut(), scalar(item)) {
+                (Some((_, items)), Some(item)) => items.push(item),
+                _ => diagnostics.push(malformed(i, line)),
+            }
+            continue;
+        }
+        if let Some((key, items)) = yaml_list.take() {
+            entries.insert(key, Value::List(items));
+        }
+
+        let (key, unparsed) = match entry(line, format) {
+            Some(e) => e,
+            None => {
+                diagnostics.push(malformed(i, line));
+                continue;
+            }
+        };
+        if format == Format::Yaml && unparsed.trim().is_empty() {
+            yaml_list = Some((key.to_string(), Vec::new()));
+            continue;
+        }
+        match value(unparsed) {
+            Some(v) => {
+                entries.insert(key.to_string(), v);
+            }
+            None if format == Format::Toml && unparsed.trim_start().starts_with('[') => {
+                pending = Some((key.to_string(), unparsed.trim().to_string(), i));
+            }
+            None => diagnostics.push(malformed(i, line)),
+        }
+    }
+    if let Some((key, items)) = yaml_list {
+        entries.insert(key, Value::List(items));
+    }
+
+    Some((FrontMatter { format, entries }, end + 1, diagnostics))
+}
+
+fn malformed(line_num: usize, line: &str) -> Diagnostic {
+    Diagnostic::new(
+        DiagnosticCode::MalformedFrontMatter,
+        line_num,
+        0..line.chars().count(),
+        "front-matter entry could not be parsed and is ignored",
+    )
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    use super::{Format, FrontMatter, Value};
+
+    fn lines(l: &[&str]) -> Vec<String> {
+        l.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn values() {
+        use super::value;
+
+        let s = |s: &str| Some(Value::String(s.to_string()));
+        let l = |l: &[&str]| Some(Value::List(lines(l)));
+        assert_eq!(value(r#" "a \"b\" \\c" # foo"#), s(r#"a "b" \c"#));
+        assert_eq!(value(r" '\textbf{x}'"), s(r"\textbf{x}"));
+        assert_eq!(value(" 12pt # foo"), s("12pt"));
+        assert_eq!(value(" C# and F#"), s("C# and F#"));
+        assert_eq!(value(r#" ["a", 'b,c', d ] # foo"#), l(&["a", "b,c", "d"]));
+        assert_eq!(value(" []"), l(&[]));
+        assert_eq!(value(""), None);
+        assert_eq!(value(r#" "a" b"#), None);
+        assert_eq!(value(r#" "a"#), None);
+        assert_eq!(value(" [a b, c"), None);
+    }
+
+    #[test]
+    fn parse_toml() {
+        use super::parse;
+
+        let input = lines(&[
+            "+++",
+            "# Metadata",
+            r#"title = "Report \\today""#,
+            "packages = [",
+            "  \"amsmath\",",
+            "  \"[utf8]{inputenc}\",",
+            "]",
+            "draft = true",
+            "foo",
+            "+++",
+            "# section: Intro",
+        ]);
+        let (front_matter, end, diagnostics) = parse(&input).unwrap();
+        assert_eq!(end, 10);
+        assert_eq!(front_matter.format, Format::Toml);
+        assert_eq!(
+            front_matter.get("title"),
+            Some(&Value::String(r"Report \today".to_string()))
+        );
+        assert_eq!(
+            front_matter.get("packages"),
+            Some(&Value::List(lines(&["amsmath", "[utf8]{inputenc}"])))
+        );
+        assert_eq!(front_matter.entries.len(), 3);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 8);
+
+        assert_eq!(parse(&lines(&["+++", "title = 'x'"])), None);
+        assert_eq!(parse(&lines(&["", "+++", "+++"])), None);
+        assert_eq!(parse(&lines(&[])), None);
+    }
+
+    #[test]
+    fn parse_yaml() {
+        use super::parse;
+
+        let input = lines(&[
+            "---",
+            "title: 'Report: Part 1'",
+            "author:",
+            "  - Jane Doe",
+            "  - \"John Doe\"",
+            "classoptions: [a4paper, 11pt]",
+            "date:",
+            "- stray",
+            "---",
+        ]);
+        let (front_matter, end, diagnostics) = parse(&input).unwrap();
+        assert_eq!(end, 9);
+        assert_eq!(front_matter.format, Format::Yaml);
+        assert_eq!(
+            front_matter.get("title"),
+            Some(&Value::String("Report: Part 1".to_string()))
+        );
+        assert_eq!(
+            front_matter.get("author"),
+            Some(&Value::List(lines(&["Jane Doe", "John Doe"])))
+        );
+        assert_eq!(
+            front_matter.get("classoptions"),
+            Some(&Value::List(lines(&["a4paper", "11pt"])))
+        );
+        assert_eq!(
+            front_matter.get("date"),
+            Some(&Value::List(lines(&["stray"])))
+        );
+        assert!(diagnostics.is_empty());
+
+        let (_, _, diagnostics) = parse(&lines(&["---", "- a", "foo bar", "---"])).unwrap();
+        assert_eq!(
+            diagnostics.iter().map(|d| d.line).collect::<Vec<_>>(),
+            [1, 2]
+        );
+    }
+
+    #[test]
+    fn preamble() {
+        let s = |s: &str| Value::String(s.to_string());
+        let l = |l: &[&str]| Value::List(lines(l));
+        let mut front_matter = FrontMatter {
+            format: Format::Toml,
+            entries: vec![
+                ("documentclass", s("article")),
+                ("classoptions", l(&["a4paper", "11pt"])),
+                ("packages", l(&["amsmath", "[utf8]{inputenc}"])),
+                ("title", s("Report")),
+                ("author", l(&["Jane Doe", "John Doe"])),
+                ("date", s(r"\today")),
+                ("version", s("1.2")),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect(),
+        };
+        assert_eq!(
+            front_matter.preamble(),
+            lines(&[
+                r"\documentclass[a4paper,11pt]{article}",
+                r"\usepackage{amsmath}",
+                r"\usepackage[utf8]{inputenc}",
+                r"\title{Report}",
+                r"\author{Jane Doe \and John Doe}",
+                r"\date{\today}",
+            ])
+        );
+        assert_eq!(front_matter.variables().len(), 4);
+        assert_eq!(front_matter.variables()["version"], "1.2");
+
+        front_matter.entries.remove("classoptions");
+        front_matter.entries.remove("packages");
+        assert_eq!(front_matter.preamble()[0], r"\documentclass{article}");
+        assert_eq!(front_matter.preamble().len(), 4);
+    }
+}
+// LCOV_EXCL_STOP
@@ -1,7 +1,24 @@
+pub mod backend;
+mod conditionals;
+pub mod diagnostics;
+pub mod document;
+mod from_latex;
+pub mod front_matter;
 mod parsers;
 mod parsing_types;
+mod pipe_table;
 pub mod preprocessing;
+mod regions;
+mod render;
+pub mod source_map;
+pub mod templates;
 mod utils;
+mod variables;
+pub mod visitor;
+
+use crate::diagnostics::Diagnostic;
+use crate::document::Document;
+use crate::visitor::{Passthrough, Visitor};
 
 const LINESEP: &str = "\n";
 const LATEX_TO_INDENTEX_FACTOR: f64 = 1.5;
@@ -11,61 +28,675 @@ const DO_NOT_EDIT_NOTICE: &str = "\
      % THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY. %\n\
      %                                                                %\n\
      % ============================================================== %\n";
+/// Environments whose bodies are passed through without being transpiled
+pub const DEFAULT_VERBATIM_ENVIRONMENTS: [&str; 5] =
+    ["verbatim", "verbatim*", "lstlisting", "minted", "comment"];
+
+/// Environments whose itemlines are expanded into `\item`
+pub const DEFAULT_LIST_ENVIRONMENTS: [&str; 3] = ["itemize*", "enumerate*", "description*"];
+
+/// Environments whose body lines are rows which need a `\\` terminator
+pub const DEFAULT_ROW_ENVIRONMENTS: [&str; 5] =
+    ["tabular*", "array", "align*", "gather*", "*matrix"];
+
+/// Environment (or a glob pattern like `enum*`) whose itemlines are expanded into `item_command`
+pub struct ListEnvironment {
+    pub pattern: String,
+    pub item_command: String,
+}
+
+impl ListEnvironment {
+    pub fn new(pattern: String, item_command: String) -> Self {
+        Self {
+            pattern,
+            item_command,
+        }
+    }
+}
+
+/// Marker which starts an itemline in a list-like environment
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ItemMarker {
+    /// `* foo`
+    Asterisk,
+    /// `- foo`
+    Dash,
+    /// `+ foo`
+    Plus,
+    /// `1. foo` or `1) foo`
+    Numbered,
+}
+
+impl std::str::FromStr for ItemMarker {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "*" => Ok(ItemMarker::Asterisk),
+            "-" => Ok(ItemMarker::Dash),
+            "+" => Ok(ItemMarker::Plus),
+            "N." | "N)" => Ok(ItemMarker::Numbered),
+            _ => Err(format!("unknown item marker '{}'", s)),
+        }
+    }
+}
+
+/// What to do with the number of a numbered itemline
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ItemNumbering {
+    /// `\item foo`, LaTeX does the numbering
+    #[default]
+    Ignore,
+    /// `\setcounter{enumi}{N-1}` followed by `\item foo`
+    SetCounter,
+    /// `\item[N.] foo`
+    Label,
+}
+
+impl std::str::FromStr for ItemNumbering {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(ItemNumbering::Ignore),
+            "setcounter" => Ok(ItemNumbering::SetCounter),
+            "label" => Ok(ItemNumbering::Label),
+            _ => Err(format!("unknown item numbering '{}'", s)),
+        }
+    }
+}
+
+/// Sectioning commands of headinglines like `== Foo`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Headings {
+    /// Headinglines are passed through unchanged
+    #[default]
+    Off,
+    /// `=` is `\section`, `==` is `\subsection` and so on
+    Article,
+    /// `=` is `\part`, `==` is `\chapter`, `===` is `\section` and so on
+    Book,
+}
+
+impl std::str::FromStr for Headings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Headings::Off),
+            "article" => Ok(Headings::Article),
+            "book" => Ok(Headings::Book),
+            _ => Err(format!("unknown headings '{}'", s)),
+        }
+    }
+}
+
+impl Headings {
+    /// Get the sectioning command for a heading with `level` equal signs
+    pub fn command(self, level: usize) -> Option<&'static str> {
+        const ARTICLE: [&str; 5] = [
+            "section",
+            "subsection",
+            "subsubsection",
+            "paragraph",
+            "subparagraph",
+        ];
+        const BOOK: [&str; 7] = [
+            "part",
+            "chapter",
+            "section",
+            "subsection",
+            "subsubsection",
+            "paragraph",
+            "subparagraph",
+        ];
+
+        let commands: &[&str] = match self {
+            Headings::Off => &[],
+            Headings::Article => &ARTICLE,
+            Headings::Book => &BOOK,
+        };
+        commands.get(level.checked_sub(1)?).copied()
+    }
+}
 
 pub struct TranspileOptions {
     pub prepend_do_not_edit_notice: bool,
+    pub backend: backend::Backend,
+    pub verbatim_environments: Vec<String>,
+    /// The first matching pattern wins
+    pub list_environments: Vec<ListEnvironment>,
+    pub item_markers: Vec<ItemMarker>,
+    pub item_numbering: ItemNumbering,
+    /// Wrap runs of itemlines outside of list-like environments into an `itemize`
+    pub implicit_lists: bool,
+    /// Names or glob patterns of environments whose rows are terminated automatically
+    pub row_environments: Vec<String>,
+    pub headings: Headings,
+    pub templates: Vec<templates::Template>,
+    /// Names for conditional blocks like `# ?if draft:` and values for `{{name}}` variables
+    pub defines: std::collections::BTreeMap<String, String>,
 }
 
-pub fn transpile(mut lines: Vec<String>, options: &TranspileOptions) -> String {
-    use crate::parsers::process_line;
-    use crate::parsing_types::Environment;
-    use crate::parsing_types::Hashline::{OpenEnv, PlainLine};
+impl TranspileOptions {
+    /// Get the command for itemlines in an environment or `None` if it is not list-like
+    pub fn item_command(&self, environment: &str) -> Option<&str> {
+        self.list_environments
+            .iter()
+            .find(|l| crate::utils::glob_match(&l.pattern, environment))
+            .map(|l| l.item_command.as_ref())
+    }
 
-    // The number of environments is not known beforehand
-    let mut env_stack: Vec<Environment> = Vec::new();
+    pub fn is_row_environment(&self, environment: &str) -> bool {
+        self.row_environments
+            .iter()
+            .any(|r| crate::utils::glob_match(r, environment))
+    }
+}
 
+impl Default for TranspileOptions {
+    fn default() -> Self {
+        Self {
+            prepend_do_not_edit_notice: true,
+            backend: backend::Backend::default(),
+            verbatim_environments: DEFAULT_VERBATIM_ENVIRONMENTS
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+            list_environments: DEFAULT_LIST_ENVIRONMENTS
+                .iter()
+                .map(|e| ListEnvironment::new(e.to_string(), "item".to_string()))
+                .collect(),
+            item_markers: vec![ItemMarker::Asterisk],
+            item_numbering: ItemNumbering::default(),
+            implicit_lists: false,
+            row_environments: Vec::new(),
+            headings: Headings::default(),
+            templates: Vec::new(),
+            defines: Default::default(),
+        }
+    }
+}
+
+pub struct Transpiled {
+    pub text: String,
+    pub diagnostics: Vec<Diagnostic>,
+    pub source_map: source_map::SourceMap,
+}
+
+pub fn transpile(lines: Vec<String>, options: &TranspileOptions) -> String {
+    transpile_checked(lines, options).text
+}
+
+/// Transpile and additionally report suspicious input which is passed through unchanged
+pub fn transpile_checked(lines: Vec<String>, options: &TranspileOptions) -> Transpiled {
+    transpile_with_visitor(lines, options, &mut Passthrough)
+}
+
+/// Like `transpile_checked`, but let a visitor rewrite or drop nodes before they are emitted
+pub fn transpile_with_visitor(
+    lines: Vec<String>,
+    options: &TranspileOptions,
+    visitor: &mut dyn Visitor,
+) -> Transpiled {
     // Input size is the sum of all line lengths plus the number of lines (for lineseps)
     let input_size = lines.iter().fold(0, |sum, l| sum + l.len()) + lines.len();
     // We do not know how much larger the transpiled LaTeX file will be, but we can guess...
     let indentex_size = (LATEX_TO_INDENTEX_FACTOR * (input_size as f64)).round() as usize;
-    let mut transpiled = String::with_capacity(indentex_size);
 
-    let adjusted_indents = crate::utils::scan_indents(lines.as_ref());
+    let (document, diagnostics) = parse_checked(lines, options);
+    let mut transpiled = render_with_capacity(&document, options, visitor, indentex_size);
+    transpiled.diagnostics = diagnostics;
 
-    if options.prepend_do_not_edit_notice {
-        transpiled.push_str(DO_NOT_EDIT_NOTICE);
+    transpiled
+}
+
+/// Convert plain LaTeX lines into indentex
+///
+/// Only environments whose `\begin` and `\end` are alone on their lines are turned into
+/// hashlines, everything else is kept as it is and indented to stay inside its environment.
+pub fn from_latex(lines: &[String]) -> String {
+    from_latex::convert(lines)
+}
+
+/// Parse indentex source lines into a document tree
+pub fn parse(lines: Vec<String>, options: &TranspileOptions) -> Document {
+    parse_checked(lines, options).0
+}
+
+/// Parse indentex source lines into a document tree and report suspicious input
+///
+/// A front-matter is turned into the preamble, then templates are expanded, unselected
+/// conditional branches are dropped and `{{name}}` variables are substituted; spans and
+/// diagnostics still refer to the source lines.
+pub fn parse_checked(
+    mut lines: Vec<String>,
+    options: &TranspileOptions,
+) -> (Document, Vec<Diagnostic>) {
+    use crate::document::{Node, NodeKind};
+
+    let front_matter = front_matter::parse(&lines);
+    let preprocess = |l: &String| conditionals::is_conditional(l) || variables::has_placeholder(l);
+    if front_matter.is_none() && options.templates.is_empty() && !lines.iter().any(preprocess) {
+        return parse_lines(lines, options);
     }
 
-    for (line_num, line) in lines.drain(..).enumerate() {
-        let list_like_active = match env_stack.last() {
-            None => false, // No environment is active at all
-            Some(d) => d.is_list_like(),
-        };
+    let (preamble, front_matter_end, front_matter_diagnostics, defines) = match front_matter {
+        Some((front_matter, end, mut diagnostics)) => {
+            // Defines from the command line or the configuration take precedence
+            let mut defines = front_matter.variables();
+            defines.extend(options.defines.clone());
+            let preamble = front_matter.preamble();
+            let regions = vec![regions::Region::Transpiled; preamble.len()];
+            let (preamble, preamble_diagnostics) =
+                variables::substitute(preamble, &regions, &defines);
+            let preamble = preamble
+                .into_iter()
+                .map(|l| Node {
+                    kind: NodeKind::Text(l),
+                    span: 0..end,
+                })
+                .collect();
+            // The preamble is generated, so report its variables at the opening delimiter
+            diagnostics.extend(preamble_diagnostics.into_iter().map(|mut d| {
+                d.line = 0;
+                d
+            }));
+            lines.drain(..end);
+            (preamble, end, diagnostics, defines)
+        }
+        None => (Vec::new(), 0, Vec::new(), options.defines.clone()),
+    };
+
+    // Verbatim bodies and regions which are not transpiled are left as they are
+    let regions = regions::scan(&lines, options);
+    let (lines, origins) = templates::expand(lines, &regions, &options.templates);
+    let origins = origins.into_iter().map(|o| o + front_matter_end).collect();
+    let regions = regions::scan(&lines, options);
+    let (lines, origins) = conditionals::select(lines, origins, &regions, &defines);
+    let regions = regions::scan(&lines, options);
+    let (lines, mut diagnostics) = variables::substitute(lines, &regions, &defines);
+    let (mut document, parse_diagnostics) = parse_lines(lines, options);
+    diagnostics.extend(parse_diagnostics);
+    document::remap_spans(&mut document.nodes, &origins);
+    for d in diagnostics.iter_mut() {
+        d.line = origins[d.line];
+    }
+    diagnostics.extend(front_matter_diagnostics);
+    diagnostics.sort_by_key(|d| d.line);
+    document.nodes.splice(0..0, preamble);
+
+    (document, diagnostics)
+}
+
+fn parse_lines(mut lines: Vec<String>, options: &TranspileOptions) -> (Document, Vec<Diagnostic>) {
+    use crate::diagnostics::check_line;
+    use crate::document::{Environment, Node, NodeKind};
+    use crate::parsers::process_line;
+    use crate::parsing_types::Hashline::{Command, Heading, InlineEnv, Item, OpenEnv, PlainLine};
+    use crate::utils::{raw_verbatim_end_marker, transpilation_pragma};
 
-        let tl = match process_line(line, list_like_active) {
-            PlainLine(l) => l,
-            OpenEnv(e) => {
-                let tag_begin = e.latex_begin();
-                env_stack.push(e);
-                tag_begin
+    fn append(
+        node: Node,
+        env_stack: &mut [(Environment, usize, bool, Vec<Node>)],
+        root: &mut Vec<Node>,
+    ) {
+        match env_stack.last_mut() {
+            Some((_, _, _, children)) => children.push(node),
+            None => root.push(node),
+        }
+    }
+
+    /// Counter of the innermost `enumerate`, e.g. `enumii` if two of them are nested
+    fn enumerate_counter(env_stack: &[(Environment, usize, bool, Vec<Node>)]) -> Option<String> {
+        let depth = env_stack
+            .iter()
+            .filter(|(e, _, _, _)| e.name().starts_with("enumerate"))
+            .count();
+        ["i", "ii", "iii", "iv"]
+            .get(depth.checked_sub(1)?)
+            .map(|n| format!("enum{}", n))
+    }
+
+    /// Append `\\` to every row at the indentation of the first body line except the last one
+    fn terminate_rows(children: &mut Vec<Node>) {
+        use crate::utils::{classify_row, terminate_row, Row};
+
+        fn text_indent(node: &Node) -> Option<usize> {
+            match &node.kind {
+                NodeKind::Text(l) if !l.trim().is_empty() => Some(l.len() - l.trim_start().len()),
+                _ => None,
             }
+        }
+
+        let level = match children.iter().find_map(text_indent) {
+            Some(level) => level,
+            None => return,
         };
-        transpiled.push_str(&tl);
-        transpiled.push_str(LINESEP);
+        let rows = children
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| text_indent(n) == Some(level))
+            .filter_map(|(i, n)| match &n.kind {
+                NodeKind::Text(l) => Some((i, classify_row(l))),
+                _ => None,
+            })
+            .filter(|(_, r)| *r != Row::Skip)
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return;
+        }
+
+        // Deeper lines continue the row above them, so the row ends after the last one of them
+        let mut row_ends = Vec::new();
+        for (k, (begin, _)) in rows[..rows.len() - 1]
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, r))| *r == Row::Unterminated)
+        {
+            let end = (*begin..rows[k + 1].0)
+                .rev()
+                .find(|&i| match &children[i].kind {
+                    NodeKind::Text(l) => classify_row(l) != Row::Skip,
+                    _ => true,
+                })
+                .unwrap_or(*begin);
+            row_ends.push(end);
+        }
+
+        // A trailing rule like `\bottomrule` needs the last row to be terminated
+        for end in row_ends.into_iter().rev() {
+            match &mut children[end].kind {
+                NodeKind::Text(l) => {
+                    if classify_row(l) == Row::Unterminated {
+                        *l = terminate_row(l);
+                    }
+                }
+                _ => {
+                    // Environments cannot be terminated in place
+                    let line = children[end].span.end - 1;
+                    let terminator = Node {
+                        kind: NodeKind::Text(format!(r"{}\\", " ".repeat(level))),
+                        span: line..line + 1,
+                    };
+                    children.insert(end + 1, terminator);
+                }
+            }
+        }
+    }
+
+    /// Turn a complete environment into a node, rewriting its body if needed
+    fn close(
+        environment: Environment,
+        children: Vec<Node>,
+        span: std::ops::Range<usize>,
+        options: &TranspileOptions,
+    ) -> Node {
+        let (environment, mut children) = crate::pipe_table::rewrite(environment, children, &span);
+        if options.is_row_environment(environment.name()) {
+            terminate_rows(&mut children);
+        }
+        Node::new(
+            NodeKind::Environment {
+                environment,
+                children,
+            },
+            span,
+        )
+    }
+
+    // The number of environments is not known beforehand,
+    // each one is stored together with the number of its opening line, whether it is an implicit
+    // list and its children
+    let mut env_stack: Vec<(Environment, usize, bool, Vec<Node>)> = Vec::new();
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut diagnostics = Vec::new();
+
+    let adjusted_indents = crate::utils::scan_indents(lines.as_ref());
+    let line_count = lines.len();
+
+    // Implicit lists are continued as long as the next non-blank line is an itemline
+    let mut next_is_item = vec![false; line_count + 1];
+    if options.implicit_lists {
+        for i in (0..line_count).rev() {
+            next_is_item[i] = if lines[i].trim().is_empty() {
+                next_is_item[i + 1]
+            } else {
+                crate::parsers::itemline_parser(&lines[i], &options.item_markers).is_ok()
+            };
+        }
+    }
+
+    // End marker of a raw verbatim environment which is currently open
+    let mut raw_verbatim_end: Option<String> = None;
+    // Whether transpilation has been switched off by a pragma
+    let mut transpilation_off = false;
+
+    let mut physical_lines = lines.drain(..).enumerate();
+    while let Some((line_num, mut line)) = physical_lines.next() {
+        // The logical line spans everything up to `end`
+        let mut end = line_num + 1;
+
+        let in_verbatim_environment = env_stack.last().is_some_and(|(e, _, _, _)| {
+            options.verbatim_environments.iter().any(|v| v == e.name())
+        });
+        if transpilation_off {
+            if transpilation_pragma(&line) == Some(true) {
+                transpilation_off = false;
+            }
+            append(
+                Node::new(NodeKind::Text(line), line_num..end),
+                &mut env_stack,
+                &mut nodes,
+            );
+        } else if in_verbatim_environment || raw_verbatim_end.is_some() {
+            // Verbatim bodies are passed through as they are
+            if raw_verbatim_end
+                .as_ref()
+                .is_some_and(|marker| line.contains(marker.as_str()))
+            {
+                raw_verbatim_end = None;
+            }
+            append(
+                Node::new(NodeKind::Text(line), line_num..end),
+                &mut env_stack,
+                &mut nodes,
+            );
+        } else {
+            // Hashlines ending with a backslash are continued on the next line
+            let next_lines = physical_lines.by_ref().map(|(_, next)| next);
+            let (logical, joined) = crate::utils::join_continuation_lines(line, next_lines);
+            line = logical;
+            end += joined;
+
+            transpilation_off = transpilation_pragma(&line) == Some(false);
+
+            let is_item = || crate::parsers::itemline_parser(&line, &options.item_markers).is_ok();
+            if options.implicit_lists && is_item() {
+                // Bare itemlines open an `itemize`, more indented ones a nested one
+                let indent = line.len() - line.trim_start_matches(' ').len();
+                let opens_list = match env_stack.last() {
+                    Some((d, _, implicit, _)) if d.is_list_like() => {
+                        *implicit && indent > d.indent_depth()
+                    }
+                    _ => true,
+                };
+                if opens_list {
+                    let list = Environment::new(
+                        indent,
+                        "itemize".to_string(),
+                        String::new(),
+                        String::new(),
+                        true,
+                    );
+                    env_stack.push((list, line_num, true, Vec::new()));
+                }
+            }
+
+            let list_like_active = match env_stack.last() {
+                None => false, // No environment is active at all
+                Some((d, _, _, _)) => d.is_list_like(),
+            };
+
+            // Itemlines wrapped in an implicit list are in a list-like environment already
+            if let Some(d) = check_line(&line, line_num, list_like_active, &options.item_markers) {
+                diagnostics.push(d);
+            }
+
+            // Headinglines are only recognised outside of environments and directly in the
+            // document, e.g. not in math environments
+            let headings_active = match env_stack.last() {
+                None => true,
+                Some((d, _, _, _)) => d.name() == "document",
+            };
+
+            let span = line_num..end;
+            match process_line(line, list_like_active, headings_active, options) {
+                PlainLine(l) => {
+                    raw_verbatim_end = raw_verbatim_end_marker(&l, &options.verbatim_environments);
+                    append(
+                        Node::new(NodeKind::Text(l), span),
+                        &mut env_stack,
+                        &mut nodes,
+                    )
+                }
+                InlineEnv(e) => append(
+                    Node::new(NodeKind::InlineEnvironment(e), span),
+                    &mut env_stack,
+                    &mut nodes,
+                ),
+                Command(c) => append(
+                    Node::new(NodeKind::Command(c), span),
+                    &mut env_stack,
+                    &mut nodes,
+                ),
+                Heading(h, label) => {
+                    append(
+                        Node::new(NodeKind::Command(h), span.clone()),
+                        &mut env_stack,
+                        &mut nodes,
+                    );
+                    if let Some(l) = label {
+                        append(
+                            Node::new(NodeKind::Command(l), span),
+                            &mut env_stack,
+                            &mut nodes,
+                        );
+                    }
+                }
+                Item(mut i) => {
+                    // Itemlines are only recognised in list-like environments
+                    if let Some(c) = env_stack
+                        .last()
+                        .and_then(|(e, _, _, _)| options.item_command(e.name()))
+                    {
+                        i.set_command(c);
+                    }
+                    if let Some(n) = i.number() {
+                        // LaTeX can only set the counter of enumerate, otherwise fall back to a label
+                        let counter = enumerate_counter(&env_stack);
+                        match (options.item_numbering, counter) {
+                            (ItemNumbering::Ignore, _) => {}
+                            (ItemNumbering::SetCounter, Some(counter)) => {
+                                let setcounter = crate::document::Command::new(
+                                    i.indent_depth(),
+                                    "setcounter".to_string(),
+                                    String::new(),
+                                    vec![counter, (n as i64 - 1).to_string()],
+                                    String::new(),
+                                );
+                                append(
+                                    Node::new(NodeKind::Command(setcounter), span.clone()),
+                                    &mut env_stack,
+                                    &mut nodes,
+                                )
+                            }
+                            (ItemNumbering::SetCounter, None) | (ItemNumbering::Label, _) => {
+                                i.set_label(format!("{}.", n))
+                            }
+                        }
+                    }
+                    append(
+                        Node::new(NodeKind::Item(i), span),
+                        &mut env_stack,
+                        &mut nodes,
+                    )
+                }
+                OpenEnv(mut e) => {
+                    e.set_list_like(options.item_command(e.name()).is_some());
+                    env_stack.push((e, line_num, false, Vec::new()))
+                }
+            };
+        }
+
+        // Indentation inside raw verbatim environments and regions which are not transpiled
+        // is arbitrary and must not close anything
+        if raw_verbatim_end.is_some() || transpilation_off {
+            continue;
+        }
 
         // Check if we are in an environment and close as many as needed
         while match env_stack.last() {
             None => false,
-            Some(d) => d.indent_depth() >= adjusted_indents[line_num + 1],
+            // Implicit lists are closed on dedent or by anything but an itemline
+            Some((d, _, true, _)) => {
+                d.indent_depth() > adjusted_indents[end]
+                    || (d.indent_depth() == adjusted_indents[end] && !next_is_item[end])
+            }
+            Some((d, _, false, _)) => d.indent_depth() >= adjusted_indents[end],
         } {
             // `unwrap()` is safe here since we have already checked if the stack is empty
-            let tag_end = env_stack.pop().unwrap().latex_end();
-            transpiled.push_str(&tag_end);
-            transpiled.push_str(LINESEP);
+            let (environment, begin_line_num, _, children) = env_stack.pop().unwrap();
+            let node = close(environment, children, begin_line_num..end, options);
+            append(node, &mut env_stack, &mut nodes);
         }
     }
 
-    transpiled
+    // Only an unterminated raw verbatim environment or a missing `% indentex: on` pragma
+    // can leave environments open
+    while let Some((environment, begin_line_num, _, children)) = env_stack.pop() {
+        let node = close(environment, children, begin_line_num..line_count, options);
+        append(node, &mut env_stack, &mut nodes);
+    }
+
+    (Document { nodes }, diagnostics)
+}
+
+/// Render a document tree into LaTeX
+pub fn render(document: &Document, options: &TranspileOptions) -> Transpiled {
+    render_with_visitor(document, options, &mut Passthrough)
+}
+
+/// Render a document tree into LaTeX and let a visitor rewrite or drop nodes on the way
+pub fn render_with_visitor(
+    document: &Document,
+    options: &TranspileOptions,
+    visitor: &mut dyn Visitor,
+) -> Transpiled {
+    render_with_capacity(document, options, visitor, 0)
+}
+
+fn render_with_capacity(
+    document: &Document,
+    options: &TranspileOptions,
+    visitor: &mut dyn Visitor,
+    capacity: usize,
+) -> Transpiled {
+    let mut emitter = crate::render::Emitter::with_capacity(capacity, options.backend, visitor);
+
+    if options.prepend_do_not_edit_notice {
+        for l in DO_NOT_EDIT_NOTICE.lines() {
+            emitter.emit_line(l, None);
+        }
+    }
+    emitter.emit_nodes(&document.nodes);
+
+    let (text, source_map) = emitter.finish();
+    Transpiled {
+        text,
+        diagnostics: Vec::new(),
+        source_map,
+    }
 }
 
 // LCOV_EXCL_START
@@ -92,6 +723,7 @@ mod tests {
                             \n $ 1 + \\frac{1}{2}$\n";
             let to = TranspileOptions {
                 prepend_do_not_edit_notice: false,
+                ..Default::default()
             };
             assert_eq!(&transpile(input, &to), expected);
         }
@@ -106,6 +738,962 @@ mod tests {
             let expected = "\\foo{bar} % qux\n  \\foo[opts]{bar}\n";
             let to = TranspileOptions {
                 prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            assert_eq!(&transpile(input, &to), expected);
+        }
+
+        #[test]
+        fn multiple_args() {
+            let input = (vec![
+                "# href: https://x.org | the site",
+                "# minipage[t]: 0.5\\textwidth | % qux",
+                "  # tabular: c|c |",
+                "    a & b",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+
+            let expected = "\\href{https://x.org}{the site}\n\
+                            \\begin{minipage}[t]{0.5\\textwidth} % qux\n  \
+                            \\begin{tabular}{c|c}\n    \
+                            a & b\n  \
+                            \\end{tabular}\n\
+                            \\end{minipage}\n";
+            let to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            assert_eq!(&transpile(input, &to), expected);
+        }
+
+        #[test]
+        fn verbatim_environments() {
+            let input = (vec![
+                "# figure:",
+                "  # lstlisting[language=Python]:",
+                "    # include: foo",
+                "    def f(x):",
+                "        return x \\",
+                "",
+                "        # bar: baz",
+                "  \\begin{verbatim}",
+                "* bullet",
+                "# section: foo",
+                "\\end{verbatim}",
+                "  # mycode:",
+                "    # section: foo",
+                "  # itemize:",
+                "    * \\begin{verbatim}x\\end{verbatim}",
+                "    * bar",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+
+            let expected = "\\begin{figure}\n  \
+                            \\begin{lstlisting}[language=Python]\n    \
+                            # include: foo\n    \
+                            def f(x):\n        \
+                            return x \\\n\
+                            \n        \
+                            # bar: baz\n  \
+                            \\end{lstlisting}\n  \
+                            \\begin{verbatim}\n\
+                            * bullet\n\
+                            # section: foo\n\
+                            \\end{verbatim}\n  \
+                            \\begin{mycode}\n    \
+                            # section: foo\n  \
+                            \\end{mycode}\n  \
+                            \\begin{itemize}\n    \
+                            \\item \\begin{verbatim}x\\end{verbatim}\n    \
+                            \\item bar\n  \
+                            \\end{itemize}\n\
+                            \\end{figure}\n";
+            let mut to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            to.verbatim_environments.push("mycode".to_string());
+            assert_eq!(&transpile(input, &to), expected);
+        }
+
+        #[test]
+        fn list_environments() {
+            use super::super::ListEnvironment;
+
+            let input = (vec![
+                "# thebibliography: 9",
+                "  * {knuth} D. Knuth",
+                "# tasks(2):",
+                "  * foo",
+                "  # inparaenum:",
+                "    * bar",
+                "# itemize:",
+                "  * baz",
+                "# myitemize:",
+                "  * qux",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+
+            let expected = "\\thebibliography{9}\n  \
+                            * {knuth} D. Knuth\n\
+                            \\begin{tasks}(2)\n  \
+                            \\task foo\n  \
+                            \\begin{inparaenum}\n    \
+                            \\item bar\n  \
+                            \\end{inparaenum}\n\
+                            \\end{tasks}\n\
+                            \\begin{itemize}\n  \
+                            \\item baz\n\
+                            \\end{itemize}\n\
+                            \\begin{myitemize}\n  \
+                            * qux\n\
+                            \\end{myitemize}\n";
+            let mut to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            to.list_environments.insert(
+                0,
+                ListEnvironment::new("task?".to_string(), "task".to_string()),
+            );
+            to.list_environments.push(ListEnvironment::new(
+                "inpara*".to_string(),
+                "item".to_string(),
+            ));
+            assert_eq!(&transpile(input, &to), expected);
+
+            let input = (vec!["# thebibliography: 9 |", "  * {knuth} D. Knuth"])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect();
+            let expected = "\\begin{thebibliography}{9}\n  \
+                            \\bibitem {knuth} D. Knuth\n\
+                            \\end{thebibliography}\n";
+            to.list_environments.push(ListEnvironment::new(
+                "thebibliography".to_string(),
+                "bibitem".to_string(),
+            ));
+            assert_eq!(&transpile(input, &to), expected);
+        }
+
+        #[test]
+        fn item_markers() {
+            use super::super::{ItemMarker, ItemNumbering};
+
+            let input = || -> Vec<String> {
+                (vec![
+                    "# itemize:",
+                    "  - foo",
+                    "  + bar",
+                    "  -- baz",
+                    "  # enumerate:",
+                    "    3. qux",
+                    "    4) quux",
+                    "    # enumerate:",
+                    "      1. corge",
+                    "    -1. grault",
+                    "# description:",
+                    "  2. garply",
+                ])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect()
+            };
+
+            let mut to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            let expected = "\\begin{itemize}\n  \
+                            - foo\n  \
+                            + bar\n  \
+                            -- baz\n  \
+                            \\begin{enumerate}\n    \
+                            3. qux\n    \
+                            4) quux\n    \
+                            \\begin{enumerate}\n      \
+                            1. corge\n    \
+                            \\end{enumerate}\n    \
+                            -1. grault\n  \
+                            \\end{enumerate}\n\
+                            \\end{itemize}\n\
+                            \\begin{description}\n  \
+                            2. garply\n\
+                            \\end{description}\n";
+            assert_eq!(&transpile(input(), &to), expected);
+
+            to.item_markers = vec![
+                ItemMarker::Asterisk,
+                ItemMarker::Dash,
+                ItemMarker::Plus,
+                ItemMarker::Numbered,
+            ];
+            let expected = "\\begin{itemize}\n  \
+                            \\item foo\n  \
+                            \\item bar\n  \
+                            -- baz\n  \
+                            \\begin{enumerate}\n    \
+                            \\item qux\n    \
+                            \\item quux\n    \
+                            \\begin{enumerate}\n      \
+                            \\item corge\n    \
+                            \\end{enumerate}\n    \
+                            -1. grault\n  \
+                            \\end{enumerate}\n\
+                            \\end{itemize}\n\
+                            \\begin{description}\n  \
+                            \\item garply\n\
+                            \\end{description}\n";
+            assert_eq!(&transpile(input(), &to), expected);
+
+            to.item_numbering = ItemNumbering::Label;
+            let expected = "\\begin{itemize}\n  \
+                            \\item foo\n  \
+                            \\item bar\n  \
+                            -- baz\n  \
+                            \\begin{enumerate}\n    \
+                            \\item[3.] qux\n    \
+                            \\item[4.] quux\n    \
+                            \\begin{enumerate}\n      \
+                            \\item[1.] corge\n    \
+                            \\end{enumerate}\n    \
+                            -1. grault\n  \
+                            \\end{enumerate}\n\
+                            \\end{itemize}\n\
+                            \\begin{description}\n  \
+                            \\item[2.] garply\n\
+                            \\end{description}\n";
+            assert_eq!(&transpile(input(), &to), expected);
+
+            to.item_numbering = ItemNumbering::SetCounter;
+            let expected = "\\begin{itemize}\n  \
+                            \\item foo\n  \
+                            \\item bar\n  \
+                            -- baz\n  \
+                            \\begin{enumerate}\n    \
+                            \\setcounter{enumi}{2}\n    \
+                            \\item qux\n    \
+                            \\setcounter{enumi}{3}\n    \
+                            \\item quux\n    \
+                            \\begin{enumerate}\n      \
+                            \\setcounter{enumii}{0}\n      \
+                            \\item corge\n    \
+                            \\end{enumerate}\n    \
+                            -1. grault\n  \
+                            \\end{enumerate}\n\
+                            \\end{itemize}\n\
+                            \\begin{description}\n  \
+                            \\item[2.] garply\n\
+                            \\end{description}\n";
+            assert_eq!(&transpile(input(), &to), expected);
+
+            // Itemlines with any configured marker are reported outside of lists, unless an
+            // implicit list wraps them
+            let input = || -> Vec<String> {
+                (vec!["- a", "1. b", "* c"])
+                    .into_iter()
+                    .map(|s| s.to_string())
+                    .collect()
+            };
+            let lines = |to: &TranspileOptions| {
+                super::super::transpile_checked(input(), to)
+                    .diagnostics
+                    .iter()
+                    .map(|d| (d.code, d.line))
+                    .collect::<Vec<_>>()
+            };
+            let outside = super::super::diagnostics::DiagnosticCode::ItemOutsideList;
+            assert_eq!(lines(&to), [(outside, 0), (outside, 1), (outside, 2)]);
+            to.implicit_lists = true;
+            assert_eq!(lines(&to), []);
+        }
+
+        #[test]
+        fn implicit_lists() {
+            use super::super::transpile_checked;
+
+            let input = || -> Vec<String> {
+                (vec![
+                    "foo",
+                    "* bar",
+                    "  continued",
+                    "",
+                    "* baz",
+                    "  * qux",
+                    "  * quux",
+                    "    * corge",
+                    "* grault",
+                    "garply",
+                    "# center:",
+                    "  * waldo",
+                    "  fred",
+                    "# enumerate:",
+                    "  * plugh",
+                    "    * xyzzy",
+                ])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect()
+            };
+
+            let mut to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            assert_eq!(transpile_checked(input(), &to).diagnostics.len(), 7);
+
+            to.implicit_lists = true;
+            let expected = "foo\n\
+                            \\begin{itemize}\n\
+                            \\item bar\n  \
+                            continued\n\
+                            \n\
+                            \\item baz\n  \
+                            \\begin{itemize}\n  \
+                            \\item qux\n  \
+                            \\item quux\n    \
+                            \\begin{itemize}\n    \
+                            \\item corge\n    \
+                            \\end{itemize}\n  \
+                            \\end{itemize}\n\
+                            \\item grault\n\
+                            \\end{itemize}\n\
+                            garply\n\
+                            \\begin{center}\n  \
+                            \\begin{itemize}\n  \
+                            \\item waldo\n  \
+                            \\end{itemize}\n  \
+                            fred\n\
+                            \\end{center}\n\
+                            \\begin{enumerate}\n  \
+                            \\item plugh\n    \
+                            \\item xyzzy\n\
+                            \\end{enumerate}\n";
+            let transpiled = transpile_checked(input(), &to);
+            assert_eq!(&transpiled.text, expected);
+            assert!(transpiled.diagnostics.is_empty());
+
+            // Blank lines before an itemline do not open a list
+            let input = (vec!["# center:", "  foo", "", "  * bar", "baz", "", "* qux"])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect();
+            let expected = "\\begin{center}\n  \
+                            foo\n\
+                            \n  \
+                            \\begin{itemize}\n  \
+                            \\item bar\n  \
+                            \\end{itemize}\n\
+                            \\end{center}\n\
+                            baz\n\
+                            \n\
+                            \\begin{itemize}\n\
+                            \\item qux\n\
+                            \\end{itemize}\n";
+            assert_eq!(&transpile(input, &to), expected);
+        }
+
+        #[test]
+        fn row_environments() {
+            use super::super::DEFAULT_ROW_ENVIRONMENTS;
+
+            let input = || -> Vec<String> {
+                (vec![
+                    "# tabular{ll}:",
+                    "  \\toprule",
+                    "  foo & bar",
+                    "  \\midrule",
+                    "  % comment",
+                    "  baz & qux \\\\",
+                    "",
+                    "  corge & grault % garply",
+                    "  \\bottomrule",
+                    "# align*:",
+                    "  a &= b",
+                    "    + c",
+                    "  # pmatrix:",
+                    "    1 & 0",
+                    "    0 & 1",
+                    "  d &= e",
+                    "# center:",
+                    "  foo",
+                    "  bar",
+                ])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect()
+            };
+
+            let mut to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            let expected = "\\begin{tabular}{ll}\n  \
+                            \\toprule\n  \
+                            foo & bar\n  \
+                            \\midrule\n  \
+                            % comment\n  \
+                            baz & qux \\\\\n\
+                            \n  \
+                            corge & grault % garply\n  \
+                            \\bottomrule\n\
+                            \\end{tabular}\n\
+                            \\begin{align*}\n  \
+                            a &= b\n    \
+                            + c\n  \
+                            \\begin{pmatrix}\n    \
+                            1 & 0\n    \
+                            0 & 1\n  \
+                            \\end{pmatrix}\n  \
+                            d &= e\n\
+                            \\end{align*}\n\
+                            \\begin{center}\n  \
+                            foo\n  \
+                            bar\n\
+                            \\end{center}\n";
+            assert_eq!(&transpile(input(), &to), expected);
+
+            to.row_environments
+                .extend(DEFAULT_ROW_ENVIRONMENTS.iter().map(|e| e.to_string()));
+            let expected = "\\begin{tabular}{ll}\n  \
+                            \\toprule\n  \
+                            foo & bar \\\\\n  \
+                            \\midrule\n  \
+                            % comment\n  \
+                            baz & qux \\\\\n\
+                            \n  \
+                            corge & grault \\\\ % garply\n  \
+                            \\bottomrule\n\
+                            \\end{tabular}\n\
+                            \\begin{align*}\n  \
+                            a &= b\n    \
+                            + c\n  \
+                            \\begin{pmatrix}\n    \
+                            1 & 0 \\\\\n    \
+                            0 & 1\n  \
+                            \\end{pmatrix}\n  \
+                            \\\\\n  \
+                            d &= e\n\
+                            \\end{align*}\n\
+                            \\begin{center}\n  \
+                            foo\n  \
+                            bar\n\
+                            \\end{center}\n";
+            assert_eq!(&transpile(input(), &to), expected);
+
+            let input = (vec!["# tabular{ll}:", "  % todo", "  \\hline", "    % todo"])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect();
+            let expected = "\\begin{tabular}{ll}\n  \
+                            % todo\n  \
+                            \\hline\n    \
+                            % todo\n\
+                            \\end{tabular}\n";
+            assert_eq!(&transpile(input, &to), expected);
+            let input = (vec!["# tabular{ll}:", "  % todo"])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect();
+            assert_eq!(
+                &transpile(input, &to),
+                "\\begin{tabular}{ll}\n  % todo\n\\end{tabular}\n"
+            );
+        }
+
+        #[test]
+        fn pipe_tables() {
+            use super::super::parse;
+
+            let input = (vec![
+                "# table[booktabs]: % foo",
+                "  | Name | Value |",
+                "  |:-----|------:|",
+                "  | $x$  | 1     |",
+                "",
+                "  | $y \\| z$ | 2 | 3 | % bar",
+                "# table[hline]:",
+                "  | a & b | c |",
+                "# table:",
+                "  | a | b |",
+                "# table[h]:",
+                "  | a | b |",
+                "# table:",
+                "  \\centering",
+                "  | a | b |",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+
+            let expected = "\\begin{tabular}{lrl} % foo\n  \
+                            \\toprule\n  \
+                            Name & Value \\\\\n  \
+                            \\midrule\n  \
+                            $x$ & 1 \\\\\n\
+                            \n  \
+                            $y \\| z$ & 2 & 3 \\\\ % bar\n  \
+                            \\bottomrule\n\
+                            \\end{tabular}\n\
+                            \\begin{tabular}{ll}\n  \
+                            \\hline\n  \
+                            a \\& b & c \\\\\n  \
+                            \\hline\n\
+                            \\end{tabular}\n\
+                            \\begin{table}\n  \
+                            | a | b |\n\
+                            \\end{table}\n\
+                            \\begin{table}[h]\n  \
+                            | a | b |\n\
+                            \\end{table}\n\
+                            \\begin{table}\n  \
+                            \\centering\n  \
+                            | a | b |\n\
+                            \\end{table}\n";
+            let to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            assert_eq!(&transpile(input.clone(), &to), expected);
+
+            // Rules are attributed to the opening line and to the last row
+            let document = parse(input, &to);
+            let spans = match &document.nodes[0].kind {
+                crate::document::NodeKind::Environment { children, .. } => {
+                    children.iter().map(|c| c.span.clone()).collect::<Vec<_>>()
+                }
+                k => panic!("not an environment: {:?}", k),
+            };
+            assert_eq!(spans, vec![0..1, 1..2, 2..3, 3..4, 4..5, 5..6, 5..6]);
+        }
+
+        #[test]
+        fn headings() {
+            use super::super::Headings;
+
+            let input = || -> Vec<String> {
+                (vec![
+                    "= Introduction",
+                    "== Motivation {#sec:motivation} % foo",
+                    "===* Aside",
+                    "# document:",
+                    "  ==== Deep",
+                    "  # align*:",
+                    "    x",
+                    "    = y",
+                    "  # itemize:",
+                    "    * bar",
+                    "    = baz",
+                    "=",
+                    "==foo",
+                    "a == b",
+                    "====== Too deep",
+                ])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect()
+            };
+
+            let mut to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            let unchanged = "= Introduction\n\
+                             == Motivation {#sec:motivation} % foo\n\
+                             ===* Aside\n\
+                             \\begin{document}\n  \
+                             ==== Deep\n  \
+                             \\begin{align*}\n    \
+                             x\n    \
+                             = y\n  \
+                             \\end{align*}\n  \
+                             \\begin{itemize}\n    \
+                             \\item bar\n    \
+                             = baz\n  \
+                             \\end{itemize}\n\
+                             \\end{document}\n\
+                             =\n\
+                             ==foo\n\
+                             a == b\n\
+                             ====== Too deep\n";
+            assert_eq!(&transpile(input(), &to), unchanged);
+
+            to.headings = Headings::Article;
+            let expected = "\\section{Introduction}\n\
+                            \\subsection{Motivation} % foo\n\
+                            \\label{sec:motivation}\n\
+                            \\subsubsection*{Aside}\n\
+                            \\begin{document}\n  \
+                            \\paragraph{Deep}\n  \
+                             \\begin{align*}\n    \
+                            x\n    \
+                            = y\n  \
+                            \\end{align*}\n  \
+                            \\begin{itemize}\n    \
+                            \\item bar\n    \
+                            = baz\n  \
+                            \\end{itemize}\n\
+                            \\end{document}\n\
+                            =\n\
+                            ==foo\n\
+                            a == b\n\
+                            ====== Too deep\n";
+            assert_eq!(&transpile(input(), &to), expected);
+
+            to.headings = Headings::Book;
+            let expected = "\\part{Introduction}\n\
+                            \\chapter{Motivation} % foo\n\
+                            \\label{sec:motivation}\n\
+                            \\section*{Aside}\n\
+                            \\begin{document}\n  \
+                            \\subsection{Deep}\n  \
+                             \\begin{align*}\n    \
+                            x\n    \
+                            = y\n  \
+                            \\end{align*}\n  \
+                            \\begin{itemize}\n    \
+                            \\item bar\n    \
+                            = baz\n  \
+                            \\end{itemize}\n\
+                            \\end{document}\n\
+                            =\n\
+                            ==foo\n\
+                            a == b\n\
+                            \\paragraph{Too deep}\n";
+            assert_eq!(&transpile(input(), &to), expected);
+        }
+
+        #[test]
+        fn templates() {
+            use super::super::templates::{parse_templates, Template};
+            use super::super::transpile_checked;
+
+            let template_file = (vec![
+                "# template: fig",
+                "  # figure[htbp]:",
+                "    \\centering",
+                "    \\includegraphics#opts{#1}",
+                "    \\caption{#2}",
+                "    \\label{#3}",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+            let input = (vec![
+                "# fig[width=.5\\linewidth]: img/plot.pdf | Caption | fig:plot",
+                "# itemize:",
+                "  * foo",
+                "  # hl: bar",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+
+            let expected = "\\begin{figure}[htbp]\n  \
+                            \\centering\n  \
+                            \\includegraphics[width=.5\\linewidth]{img/plot.pdf}\n  \
+                            \\caption{Caption}\n  \
+                            \\label{fig:plot}\n\
+                            \\end{figure}\n\
+                            \\begin{itemize}\n  \
+                            \\item foo\n  \
+                            \\textbf{\\emph{bar}}\n\
+                            \\end{itemize}\n";
+            let mut to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            to.templates = parse_templates(&template_file);
+            to.templates
+                .push(Template::new("hl".to_string(), "# textbf: \\emph{#1}"));
+            let transpiled = transpile_checked(input, &to);
+            assert_eq!(&transpiled.text, expected);
+
+            // Every expanded line refers to the template hashline
+            let source_lines = transpiled.source_map.iter().collect::<Vec<_>>();
+            assert_eq!(
+                source_lines,
+                [0, 0, 0, 0, 0, 0, 1, 2, 3, 1]
+                    .iter()
+                    .map(|&l| Some(l))
+                    .collect::<Vec<_>>()
+            );
+
+            // Verbatim bodies and regions which are not transpiled are not expanded
+            let input = (vec!["# verbatim:", "  # hl: x", "% indentex: off", "# hl: y"])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect();
+            let expected = "\\begin{verbatim}\n  \
+                            # hl: x\n\
+                            \\end{verbatim}\n\
+                            % indentex: off\n\
+                            # hl: y\n";
+            assert_eq!(&transpile(input, &to), expected);
+        }
+
+        #[test]
+        fn conditionals() {
+            use super::super::transpile_checked;
+
+            let input = || -> Vec<String> {
+                (vec![
+                    "# document:",
+                    "  # ?if draft:",
+                    "    # itemize:",
+                    "      * todo",
+                    "  # ?else:",
+                    "    \\maketitle",
+                    "  # ?if !draft:",
+                    "    # section: Intro",
+                    "  text",
+                ])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect()
+            };
+
+            let mut to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            let expected = "\\begin{document}\n  \
+                            \\maketitle\n  \
+                            \\section{Intro}\n  \
+                            text\n\
+                            \\end{document}\n";
+            let transpiled = transpile_checked(input(), &to);
+            assert_eq!(&transpiled.text, expected);
+            assert_eq!(
+                transpiled.source_map.iter().collect::<Vec<_>>(),
+                vec![Some(0), Some(5), Some(7), Some(8), Some(0)]
+            );
+
+            to.defines.insert("draft".to_string(), "true".to_string());
+            let expected = "\\begin{document}\n  \
+                            \\begin{itemize}\n    \
+                            \\item todo\n  \
+                            \\end{itemize}\n  \
+                            text\n\
+                            \\end{document}\n";
+            assert_eq!(&transpile(input(), &to), expected);
+
+            // Conditionals in verbatim bodies and regions which are not transpiled are kept
+            let input = (vec![
+                "# lstlisting:",
+                "  # ?if debug:",
+                "    log()",
+                "% indentex: off",
+                "# ?if debug:",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+            let expected = "\\begin{lstlisting}\n  \
+                            # ?if debug:\n    \
+                            log()\n\
+                            \\end{lstlisting}\n\
+                            % indentex: off\n\
+                            # ?if debug:\n";
+            assert_eq!(&transpile(input, &to), expected);
+        }
+
+        #[test]
+        fn variables() {
+            use super::super::diagnostics::DiagnosticCode::{MalformedHashline, UndefinedVariable};
+            use super::super::transpile_checked;
+
+            let input: Vec<String> = (vec![
+                "# title: Report {{version}}",
+                "# foo bar",
+                "Grant {{grant}}, see \\url{{{url}}} or \\{{url}}",
+                "  # ?if draft:",
+                "    {{draft}}",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+
+            let mut to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            to.defines.insert("version".to_string(), "1.2".to_string());
+            to.defines.insert("draft".to_string(), "true".to_string());
+            to.defines
+                .insert("url".to_string(), "https://example.org".to_string());
+            let expected = "\\title{Report 1.2}\n\
+                            # foo bar\n\
+                            Grant {{grant}}, see \\url{https://example.org} or {{url}}\n  \
+                            true\n";
+            let transpiled = transpile_checked(input, &to);
+            assert_eq!(&transpiled.text, expected);
+            assert_eq!(
+                transpiled
+                    .diagnostics
+                    .iter()
+                    .map(|d| (d.code, d.line, d.columns.clone()))
+                    .collect::<Vec<_>>(),
+                vec![(MalformedHashline, 1, 0..9), (UndefinedVariable, 2, 6..15)]
+            );
+
+            // Verbatim bodies and regions which are not transpiled are not substituted
+            let input = (vec![
+                "# lstlisting:",
+                "  {{version}}",
+                "% indentex: off",
+                "{{version}}",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+            let expected = "\\begin{lstlisting}\n  \
+                            {{version}}\n\
+                            \\end{lstlisting}\n\
+                            % indentex: off\n\
+                            {{version}}\n";
+            assert_eq!(&transpile(input, &to), expected);
+        }
+
+        #[test]
+        fn front_matter() {
+            use super::super::transpile_checked;
+
+            let input: Vec<String> = (vec![
+                "---",
+                "documentclass: article",
+                "packages: [amsmath, '[utf8]{inputenc}']",
+                "title: Report {{version}}",
+                "author:",
+                "  - Jane Doe",
+                "  - John Doe",
+                "version: 1.2",
+                "venue: ACM",
+                "foo bar",
+                "---",
+                "# document:",
+                "  # ?if venue:",
+                "    Version {{version}} for {{venue}}",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+
+            let mut to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            to.defines.insert("version".to_string(), "2.0".to_string());
+            let expected = "\\documentclass{article}\n\
+                            \\usepackage{amsmath}\n\
+                            \\usepackage[utf8]{inputenc}\n\
+                            \\title{Report 2.0}\n\
+                            \\author{Jane Doe \\and John Doe}\n\
+                            \\begin{document}\n  \
+                            Version 2.0 for ACM\n\
+                            \\end{document}\n";
+            let transpiled = transpile_checked(input, &to);
+            assert_eq!(&transpiled.text, expected);
+            assert_eq!(
+                transpiled
+                    .diagnostics
+                    .iter()
+                    .map(|d| d.line)
+                    .collect::<Vec<_>>(),
+                vec![9]
+            );
+            assert_eq!(
+                transpiled.source_map.iter().collect::<Vec<_>>(),
+                vec![
+                    Some(0),
+                    Some(0),
+                    Some(0),
+                    Some(0),
+                    Some(0),
+                    Some(11),
+                    Some(13),
+                    Some(11)
+                ]
+            );
+        }
+
+        #[test]
+        fn pragmas() {
+            let input = (vec![
+                "# foo:",
+                "  % indentex: off",
+                "\\newcommand{\\pair}[2]{",
+                "# 1: #2}",
+                "  * bar",
+                "  % indentex: on",
+                "  # bar: baz",
+                "% indentex: off",
+                "# qux:",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+
+            let expected = "\\begin{foo}\n  \
+                            % indentex: off\n\
+                            \\newcommand{\\pair}[2]{\n\
+                            # 1: #2}\n  \
+                            * bar\n  \
+                            % indentex: on\n  \
+                            \\bar{baz}\n\
+                            \\end{foo}\n\
+                            % indentex: off\n\
+                            # qux:\n";
+            let to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            assert_eq!(&transpile(input, &to), expected);
+        }
+
+        #[test]
+        fn unterminated_raw_verbatim_environment() {
+            let input = (vec!["# foo:", "  \\begin{verbatim}", "# bar: baz"])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect();
+
+            let expected = "\\begin{foo}\n  \
+                            \\begin{verbatim}\n\
+                            # bar: baz\n\
+                            \\end{foo}\n";
+            let to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            assert_eq!(&transpile(input, &to), expected);
+        }
+
+        #[test]
+        fn inline_environments() {
+            let input = (vec![
+                "# center::",
+                "  # equation*:: E = mc^2 % qux",
+                "  foo",
+                "# center[bar]::foo",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+
+            let expected = "\\begin{center} \\end{center}\n  \
+                            \\begin{equation*} E = mc^2 \\end{equation*} % qux\n  \
+                            foo\n\
+                            \\begin{center}[bar] foo \\end{center}\n";
+            let to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
             };
             assert_eq!(&transpile(input, &to), expected);
         }
@@ -120,6 +1708,7 @@ mod tests {
             let expected = "\\begin{foo}\n  bar\n\\end{foo}\n";
             let to = TranspileOptions {
                 prepend_do_not_edit_notice: false,
+                ..Default::default()
             };
             assert_eq!(&transpile(input, &to), expected);
         }
@@ -134,6 +1723,7 @@ mod tests {
             let expected = "\\begin{foo}\n  \\begin{bar}\n  \\end{bar}\n\\end{foo}\n";
             let to = TranspileOptions {
                 prepend_do_not_edit_notice: false,
+                ..Default::default()
             };
             assert_eq!(&transpile(input, &to), expected);
         }
@@ -142,12 +1732,218 @@ mod tests {
         fn do_not_edit_notice() {
             let to = TranspileOptions {
                 prepend_do_not_edit_notice: true,
+                ..Default::default()
             };
             assert_eq!(
                 &transpile(vec!["".to_string()], &to),
                 &format!("{}\n", super::super::DO_NOT_EDIT_NOTICE)
             );
         }
+
+        #[test]
+        fn diagnostics() {
+            use super::super::diagnostics::DiagnosticCode::{ItemOutsideList, MalformedHashline};
+            use super::super::transpile_checked;
+
+            let input = (vec!["# foo bar", "# itemize:", "  * foo", "* bar"])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect();
+
+            let to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            let transpiled = transpile_checked(input, &to);
+            assert_eq!(
+                transpiled.text,
+                "# foo bar\n\\begin{itemize}\n  \\item foo\n\\end{itemize}\n* bar\n"
+            );
+            assert_eq!(
+                transpiled
+                    .diagnostics
+                    .iter()
+                    .map(|d| (d.line, d.code))
+                    .collect::<Vec<_>>(),
+                vec![(0, MalformedHashline), (3, ItemOutsideList)]
+            );
+        }
+
+        #[test]
+        fn source_map() {
+            use super::super::transpile_checked;
+
+            let input = (vec!["# foo:", "  # bar:", "", "    baz", "qux"])
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect();
+
+            let to = TranspileOptions {
+                prepend_do_not_edit_notice: true,
+                ..Default::default()
+            };
+            let transpiled = transpile_checked(input, &to);
+            let notice_len = super::super::DO_NOT_EDIT_NOTICE.lines().count();
+            assert_eq!(transpiled.text.lines().count(), transpiled.source_map.len());
+            assert_eq!(
+                transpiled.source_map.iter().collect::<Vec<_>>()[..notice_len],
+                vec![None; notice_len][..]
+            );
+            assert_eq!(
+                transpiled
+                    .source_map
+                    .iter()
+                    .skip(notice_len)
+                    .collect::<Vec<_>>(),
+                vec![
+                    Some(0), // \begin{foo}
+                    Some(1), // \begin{bar}
+                    Some(2),
+                    Some(3),
+                    Some(1), // \end{bar}
+                    Some(0), // \end{foo}
+                    Some(4),
+                ]
+            );
+        }
+
+        #[test]
+        fn continuation_lines() {
+            use super::super::transpile_checked;
+
+            let input = (vec![
+                "# figure:",
+                "  # tikzpicture[scale=2,\\",
+                "every node/.style={draw}]:",
+                "    # draw: (0, 0) -- \\",
+                "      (1, 1);",
+                "  # href: https://x.org \\",
+                "    | the site \\",
+                "",
+                "  # foo: bar \\\\",
+                "  # foo: bar % baz \\",
+                "qux",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+
+            let expected = "\\begin{figure}\n  \
+                            \\begin{tikzpicture}[scale=2, every node/.style={draw}]\n    \
+                            \\draw{(0, 0) -- (1, 1);}\n  \
+                            \\end{tikzpicture}\n  \
+                            \\href{https://x.org}{the site}\n  \
+                            \\foo{bar \\\\}\n  \
+                            \\foo{bar} % baz \\\n\
+                            \\end{figure}\n\
+                            qux\n";
+            let to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            let transpiled = transpile_checked(input, &to);
+            assert_eq!(transpiled.text, expected);
+            assert_eq!(
+                transpiled.source_map.iter().collect::<Vec<_>>(),
+                vec![
+                    Some(0),
+                    Some(1),
+                    Some(3),
+                    Some(1),
+                    Some(5),
+                    Some(8),
+                    Some(9),
+                    Some(0),
+                    Some(10),
+                ]
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod parse_spec {
+        use super::super::document::{Command, Document, Environment, Item, Node, NodeKind};
+        use super::super::{parse, render, TranspileOptions};
+
+        fn input() -> Vec<String> {
+            (vec![
+                "# foo:",
+                "  # itemize:",
+                "    * bar",
+                "",
+                "  # baz: qux",
+                "quux",
+            ])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect()
+        }
+
+        #[test]
+        fn document_tree() {
+            let expected = Document {
+                nodes: vec![
+                    Node::new(
+                        NodeKind::Environment {
+                            environment: Environment::new(
+                                0,
+                                "foo".to_string(),
+                                "".to_string(),
+                                "".to_string(),
+                                false,
+                            ),
+                            children: vec![
+                                Node::new(
+                                    NodeKind::Environment {
+                                        environment: Environment::new(
+                                            2,
+                                            "itemize".to_string(),
+                                            "".to_string(),
+                                            "".to_string(),
+                                            true,
+                                        ),
+                                        children: vec![Node::new(
+                                            NodeKind::Item(Item::new(4, "bar".to_string())),
+                                            2..3,
+                                        )],
+                                    },
+                                    1..3,
+                                ),
+                                Node::new(NodeKind::Text("".to_string()), 3..4),
+                                Node::new(
+                                    NodeKind::Command(Command::new(
+                                        2,
+                                        "baz".to_string(),
+                                        "".to_string(),
+                                        vec!["qux".to_string()],
+                                        "".to_string(),
+                                    )),
+                                    4..5,
+                                ),
+                            ],
+                        },
+                        0..5,
+                    ),
+                    Node::new(NodeKind::Text("quux".to_string()), 5..6),
+                ],
+            };
+            assert_eq!(parse(input(), &Default::default()), expected);
+        }
+
+        #[test]
+        fn render_parsed_document() {
+            let to = TranspileOptions {
+                prepend_do_not_edit_notice: false,
+                ..Default::default()
+            };
+            let rendered = render(&parse(input(), &to), &to);
+            assert_eq!(
+                rendered.text,
+                "\\begin{foo}\n  \\begin{itemize}\n    \\item bar\n  \\end{itemize}\n\n  \\baz{qux}\n\\end{foo}\nquux\n"
+            );
+            assert!(rendered.diagnostics.is_empty());
+            assert_eq!(rendered.source_map.len(), 8);
+        }
     }
 }
 // LCOV_EXCL_STOP
@@ -1,4 +1,7 @@
-use crate::parsing_types::{Hashline, RawHashlineParseData, RawItemlineParseData};
+use crate::parsing_types::{
+    Hashline, RawHashlineParseData, RawHeadinglineParseData, RawItemlineParseData,
+};
+use crate::{ItemMarker, TranspileOptions};
 
 #[inline]
 fn escaped_colon(input: &str) -> nom::IResult<&str, &str> {
@@ -68,7 +71,30 @@ fn args_parser(input: &str) -> nom::IResult<&str, String> {
     )(input)
 }
 
-fn hashline_parser(input: &str) -> nom::IResult<&str, RawHashlineParseData> {
+/// Split hashline args at `|` separators with whitespaces (or the start or the end of the args)
+/// on both sides
+///
+/// Any other `|`, e.g. in `$|x|$` or in the LaTeX command `\|`, is part of an arg.
+pub fn split_args(args: &str) -> Vec<String> {
+    let mut split = Vec::new();
+    let mut begin = 0;
+    let mut previous: Option<char> = None;
+    let mut chars = args.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, n)| n);
+        let delimited = |neighbour: Option<char>| neighbour.is_none_or(char::is_whitespace);
+        if c == '|' && delimited(previous) && delimited(next) {
+            split.push(args[begin..i].trim().to_string());
+            begin = i + 1;
+        }
+        previous = Some(c);
+    }
+    split.push(args[begin..].trim().to_string());
+
+    split
+}
+
+pub fn hashline_parser(input: &str) -> nom::IResult<&str, RawHashlineParseData> {
     use crate::utils::trim_end_inplace;
     use nom::bytes::complete::{is_a, tag};
     use nom::combinator::{opt, rest};
@@ -79,6 +105,7 @@ fn hashline_parser(input: &str) -> nom::IResult<&str, RawHashlineParseData> {
     let (input, _) = opt(is_a(" \t"))(input)?;
     let (input, opts) = opts_parser(input)?;
     let (input, _) = tag(":")(input)?;
+    let (input, inline) = opt(tag(":"))(input)?;
     let (input, _) = opt(is_a(" \t"))(input)?;
     let (input, args) = args_parser(input)?;
     let (input, comment) = rest(input)?;
@@ -101,23 +128,71 @@ fn hashline_parser(input: &str) -> nom::IResult<&str, RawHashlineParseData> {
             opts: trim_end_inplace(opts),
             args: trim_end_inplace(args),
             comment: comment.trim_end().to_string(),
+            inline: inline.is_some(),
         },
     ))
 }
 
 // Itemline parsers
-fn itemline_parser(input: &str) -> nom::IResult<&str, RawItemlineParseData> {
-    use nom::bytes::complete::{is_a, tag};
+#[inline]
+fn marker_end(input: &str) -> nom::IResult<&str, ()> {
+    use nom::error::ErrorKind::Tag;
+
+    // Dashes and numbers are common in text, so such markers must be followed by a space
+    if input.is_empty() || input.starts_with(' ') {
+        Ok((input, ()))
+    } else {
+        Err(nom::Err::Error((input, Tag)))
+    }
+}
+
+#[inline]
+fn numbered_marker(input: &str) -> nom::IResult<&str, Option<usize>> {
+    use nom::character::complete::{digit1, one_of};
+    use nom::combinator::map_res;
+    use nom::sequence::terminated;
+
+    let (input, number) = map_res(terminated(digit1, one_of(".)")), |d: &str| d.parse())(input)?;
+    let (input, _) = marker_end(input)?;
+
+    Ok((input, Some(number)))
+}
+
+#[inline]
+fn item_marker(input: &str, marker: ItemMarker) -> nom::IResult<&str, Option<usize>> {
+    use nom::bytes::complete::tag;
+    use nom::combinator::map;
+    use nom::sequence::terminated;
+
+    match marker {
+        ItemMarker::Asterisk => map(tag("*"), |_| None)(input),
+        ItemMarker::Dash => map(terminated(tag("-"), marker_end), |_| None)(input),
+        ItemMarker::Plus => map(terminated(tag("+"), marker_end), |_| None)(input),
+        ItemMarker::Numbered => numbered_marker(input),
+    }
+}
+
+pub fn itemline_parser<'a>(
+    input: &'a str,
+    markers: &[ItemMarker],
+) -> nom::IResult<&'a str, RawItemlineParseData> {
+    use nom::bytes::complete::is_a;
     use nom::combinator::{opt, rest};
+    use nom::error::ErrorKind::Tag;
 
     let (input, indentation) = opt(is_a(" "))(input)?;
-    let (input, _) = tag("*")(input)?;
+    let (input, number) = markers
+        .iter()
+        .map(|&m| item_marker(input, m))
+        .find(Result::is_ok)
+        .unwrap_or(Err(nom::Err::Error((input, Tag))))?;
     let (input, item) = rest(input)?;
 
     Ok((
         input,
         RawItemlineParseData {
             indent_depth: indentation.map_or(0, |s| s.len()),
+            number,
             item: item.trim().to_string(),
         },
     ))
@@ -125,23 +200,76 @@ fn itemline_parser(input: &str) -> nom::IResult<&str, RawItemlineParseData> {
 
 // Itemline processing
 #[inline]
-fn process_itemline(line: String) -> Hashline {
+fn process_itemline(line: String, markers: &[ItemMarker]) -> Hashline {
     use self::Hashline::PlainLine;
 
-    match itemline_parser(line.as_ref()) {
+    match itemline_parser(line.as_ref(), markers) {
         Ok((_, r)) => r.into(),
         Err(_) => PlainLine(line),
     }
 }
 
-// Fully process line
-pub fn process_line(line: String, list_like_active: bool) -> Hashline {
+// Headingline parsers
+pub fn headingline_parser(input: &str) -> nom::IResult<&str, RawHeadinglineParseData> {
+    use crate::utils::trim_end_inplace;
+    use nom::bytes::complete::{is_a, tag};
+    use nom::combinator::{opt, rest, verify};
+
+    let (input, indentation) = opt(is_a(" "))(input)?;
+    let (input, level) = is_a("=")(input)?;
+    let (input, starred) = opt(tag("*"))(input)?;
+    let (input, _) = is_a(" ")(input)?;
+    let (input, title) = verify(args_parser, |t: &str| !t.trim().is_empty())(input)?;
+    let (input, comment) = rest(input)?;
+
+    // An optional label is given at the end of the title, e.g. `= Intro {#sec:intro}`
+    let title = trim_end_inplace(title);
+    let (title, label) = match title
+        .strip_suffix('}')
+        .and_then(|t| t.rfind("{#").map(|i| (&t[..i], &t[i + 2..])))
+    {
+        Some((t, l)) if !t.trim().is_empty() && !l.is_empty() => {
+            (t.trim_end().to_string(), Some(l.to_string()))
+        }
+        _ => (title, None),
+    };
+
+    Ok((
+        input,
+        RawHeadinglineParseData {
+            indent_depth: indentation.map_or(0, |s| s.len()),
+            level: level.len(),
+            starred: starred.is_some(),
+            title,
+            label,
+            comment: comment.trim_end().to_string(),
+        },
+    ))
+}
+
+// Fully process line, headinglines are only recognised if `headings_active`
+pub fn process_line(
+    line: String,
+    list_like_active: bool,
+    headings_active: bool,
+    options: &TranspileOptions,
+) -> Hashline {
     use self::Hashline::PlainLine;
 
-    match (hashline_parser(line.as_ref()), list_like_active) {
-        (Ok((_, r)), _) => r.into(),
-        (_, true) => process_itemline(line),
-        (_, false) => PlainLine(line),
+    if let Ok((_, r)) = hashline_parser(line.as_ref()) {
+        return r.into();
+    }
+    if headings_active {
+        if let Ok((_, r)) = headingline_parser(line.as_ref()) {
+            if let Some(name) = options.headings.command(r.level) {
+                return r.into_hashline(name);
+            }
+        }
+    }
+    if list_like_active {
+        process_itemline(line, &options.item_markers)
+    } else {
+        PlainLine(line)
     }
 }
 
@@ -514,7 +642,7 @@ mod tests {
 
         #[test]
         fn should_stop_at_a_terminator_or_escaped_char_after_taking_as_much_as_possible() {
-            for stop_sequence in vec![r"\", r"\%", r"\:", ":"] {
+            for stop_sequence in [r"\", r"\%", r"\:", ":"] {
                 for valid_input in opts_parser_valid_input_examples!() {
                     let expected_rest = stop_sequence.to_string() + valid_input;
                     let input_with_stop_sequence = valid_input.to_string() + expected_rest.as_ref();
@@ -599,7 +727,7 @@ mod tests {
         fn should_stop_at_a_terminator_after_taking_as_much_as_possible() {
             for terminator in ":%".chars() {
                 for valid_input in opts_parser_valid_input_with_escaped_chars_examples!() {
-                    let expected_rest = terminator.to_string() + valid_input.as_ref();
+                    let expected_rest = terminator.to_string() + valid_input;
                     let input_with_terminator = valid_input.to_string() + expected_rest.as_ref();
                     assert_eq!(
                         opts_parser(&input_with_terminator),
@@ -648,7 +776,7 @@ mod tests {
                 Ok((r": foo", r"equation {bar: qux}".to_string()))
             );
 
-            for e in vec![":E", "%E"] {
+            for e in [":E", "%E"] {
                 assert_eq!(opts_parser(e), Ok((e, "".to_string())));
             }
         }
@@ -775,7 +903,7 @@ mod tests {
 
         #[test]
         fn should_stop_at_a_terminator_or_escaped_char_after_taking_as_much_as_possible() {
-            for stop_sequence in vec!["%", r"\", r"\%"] {
+            for stop_sequence in ["%", r"\", r"\%"] {
                 for valid_input in args_parser_valid_input_examples!() {
                     let expected_rest = stop_sequence.to_string() + valid_input;
                     let input_with_stop_sequence = valid_input.to_string() + expected_rest.as_ref();
@@ -890,6 +1018,30 @@ mod tests {
         }
     }
 
+    #[cfg(test)]
+    mod split_args_spec {
+        #[test]
+        fn split_args() {
+            use super::super::split_args;
+
+            assert_eq!(split_args("foo"), ["foo"]);
+            assert_eq!(split_args(""), [""]);
+            assert_eq!(
+                split_args("https://x.org | the site"),
+                ["https://x.org", "the site"]
+            );
+            assert_eq!(split_args("a|b |  c"), ["a|b", "c"]);
+            assert_eq!(split_args("a |"), ["a", ""]);
+            assert_eq!(split_args("| a"), ["", "a"]);
+            assert_eq!(split_args("a || b"), ["a || b"]);
+            assert_eq!(split_args("$|x|$ | b"), ["$|x|$", "b"]);
+            assert_eq!(split_args(r"$\|x\|$ | b"), [r"$\|x\|$", "b"]);
+            assert_eq!(split_args(r"a\\| b"), [r"a\\| b"]);
+            assert_eq!(split_args(r"\textbf{a} | \%"), [r"\textbf{a}", r"\%"]);
+            assert_eq!(split_args(r"a\"), [r"a\"]);
+        }
+    }
+
     #[cfg(test)]
     mod hashline_parser_spec {
         use super::super::hashline_parser;
@@ -907,6 +1059,7 @@ mod tests {
                         opts: "".to_string(),
                         args: "".to_string(),
                         comment: "".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -917,6 +1070,7 @@ mod tests {
                         opts: "".to_string(),
                         args: "bar".to_string(),
                         comment: "".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -927,6 +1081,7 @@ mod tests {
                         opts: "[bar]".to_string(),
                         args: "".to_string(),
                         comment: "".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -937,6 +1092,7 @@ mod tests {
                         opts: "[bar]".to_string(),
                         args: "qux".to_string(),
                         comment: "".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -947,6 +1103,7 @@ mod tests {
                         opts: "[:]".to_string(),
                         args: "bar".to_string(),
                         comment: "".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -957,6 +1114,7 @@ mod tests {
                         opts: "".to_string(),
                         args: "".to_string(),
                         comment: "% bar".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -967,6 +1125,7 @@ mod tests {
                         opts: "".to_string(),
                         args: "bar".to_string(),
                         comment: "% baz".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -977,6 +1136,7 @@ mod tests {
                         opts: "".to_string(),
                         args: r"bar\%".to_string(),
                         comment: "% baz".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -987,6 +1147,7 @@ mod tests {
                         opts: "".to_string(),
                         args: "".to_string(),
                         comment: "".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -997,6 +1158,7 @@ mod tests {
                         opts: "".to_string(),
                         args: "".to_string(),
                         comment: "".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -1007,6 +1169,7 @@ mod tests {
                         opts: "bar".to_string(),
                         args: "".to_string(),
                         comment: "".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -1017,6 +1180,7 @@ mod tests {
                         opts: r"\bar".to_string(),
                         args: "".to_string(),
                         comment: "".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -1027,6 +1191,7 @@ mod tests {
                         opts: r"\bar".to_string(),
                         args: "".to_string(),
                         comment: "".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -1037,6 +1202,7 @@ mod tests {
                         opts: r"\bar".to_string(),
                         args: "qux".to_string(),
                         comment: "".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -1047,6 +1213,7 @@ mod tests {
                         opts: r"\bar".to_string(),
                         args: "qux".to_string(),
                         comment: "% blup".to_string(),
+                        inline: false,
                     },
                 ),
                 (
@@ -1057,6 +1224,29 @@ mod tests {
                         opts: r"bar".to_string(),
                         args: "qux".to_string(),
                         comment: "% \t blup".to_string(),
+                        inline: false,
+                    },
+                ),
+                (
+                    "  # equation*:: E = mc^2 % foo",
+                    RawHashlineParseData {
+                        indent_depth: 2,
+                        name: "equation*".to_string(),
+                        opts: "".to_string(),
+                        args: "E = mc^2".to_string(),
+                        comment: "% foo".to_string(),
+                        inline: true,
+                    },
+                ),
+                (
+                    r"# center[\:]::",
+                    RawHashlineParseData {
+                        indent_depth: 0,
+                        name: "center".to_string(),
+                        opts: "[:]".to_string(),
+                        args: "".to_string(),
+                        comment: "".to_string(),
+                        inline: true,
                     },
                 ),
             ] {
@@ -1069,7 +1259,7 @@ mod tests {
             use nom::error::ErrorKind::Tag;
             use nom::Err::Error;
 
-            for (input, expected_rest) in vec![
+            for (input, expected_rest) in [
                 (" \t# foo:", "\t# foo:"), // consume whitespace, but stopped at the tab
                 (r" \#", r"\#"),           // consume whitespace, but stopped at the backslash
                 ("#foo:", "#foo:"),        // could not consume "# "
@@ -1083,7 +1273,7 @@ mod tests {
             use nom::error::ErrorKind::Many1;
             use nom::Err::Error;
 
-            for (input, expected_rest) in vec![
+            for (input, expected_rest) in [
                 (" #  foo:", " foo:"), // consume "# " and stop immediately at the second whitespace
                 ("# [foo:", "[foo:"),
             ] {
@@ -1096,7 +1286,7 @@ mod tests {
             use nom::error::ErrorKind::Tag;
             use nom::Err::Error;
 
-            for input in vec!["# foo", "  # foo bar", r"  # foo \%    \:", "# #"] {
+            for input in ["# foo", "  # foo bar", r"  # foo \%    \:", "# #"] {
                 assert_eq!(hashline_parser(input), Err(Error(("", Tag))));
             }
         }
@@ -1105,15 +1295,17 @@ mod tests {
     #[cfg(test)]
     mod itemline_parser_spec {
         use super::super::itemline_parser;
+        use crate::ItemMarker;
 
         #[test]
         fn valid_itemlines() {
             use super::super::RawItemlineParseData;
 
-            for (input, expected_raw_parse_data) in vec![
+            for (input, expected_raw_parse_data) in [
                 (
                     "*",
                     RawItemlineParseData {
+                        number: None,
                         indent_depth: 0,
                         item: "".to_string(),
                     },
@@ -1121,6 +1313,7 @@ mod tests {
                 (
                     "*  ",
                     RawItemlineParseData {
+                        number: None,
                         indent_depth: 0,
                         item: "".to_string(),
                     },
@@ -1128,6 +1321,7 @@ mod tests {
                 (
                     "  *",
                     RawItemlineParseData {
+                        number: None,
                         indent_depth: 2,
                         item: "".to_string(),
                     },
@@ -1135,6 +1329,7 @@ mod tests {
                 (
                     "  *  ",
                     RawItemlineParseData {
+                        number: None,
                         indent_depth: 2,
                         item: "".to_string(),
                     },
@@ -1142,6 +1337,7 @@ mod tests {
                 (
                     "*foo",
                     RawItemlineParseData {
+                        number: None,
                         indent_depth: 0,
                         item: "foo".to_string(),
                     },
@@ -1149,6 +1345,7 @@ mod tests {
                 (
                     "* foo",
                     RawItemlineParseData {
+                        number: None,
                         indent_depth: 0,
                         item: "foo".to_string(),
                     },
@@ -1156,6 +1353,7 @@ mod tests {
                 (
                     "   * bar",
                     RawItemlineParseData {
+                        number: None,
                         indent_depth: 3,
                         item: "bar".to_string(),
                     },
@@ -1163,13 +1361,57 @@ mod tests {
                 (
                     "***",
                     RawItemlineParseData {
+                        number: None,
                         indent_depth: 0,
                         item: "**".to_string(),
                     },
                 ),
             ] {
-                assert_eq!(itemline_parser(input), Ok(("", expected_raw_parse_data)));
+                assert_eq!(
+                    itemline_parser(input, &[ItemMarker::Asterisk]),
+                    Ok(("", expected_raw_parse_data))
+                );
+            }
+        }
+
+        #[test]
+        fn alternative_markers() {
+            use super::super::RawItemlineParseData;
+            use nom::error::ErrorKind::Tag;
+            use nom::Err::Error;
+
+            let markers = [
+                ItemMarker::Asterisk,
+                ItemMarker::Dash,
+                ItemMarker::Plus,
+                ItemMarker::Numbered,
+            ];
+            for (input, number, item) in [
+                ("* foo", None, "foo"),
+                ("  - foo", None, "foo"),
+                ("-", None, ""),
+                ("+ foo", None, "foo"),
+                ("  3. foo", Some(3), "foo"),
+                ("12) foo", Some(12), "foo"),
+                ("1.", Some(1), ""),
+            ] {
+                assert_eq!(
+                    itemline_parser(input, &markers),
+                    Ok((
+                        "",
+                        RawItemlineParseData {
+                            number,
+                            indent_depth: input.len() - input.trim_start().len(),
+                            item: item.to_string(),
+                        }
+                    ))
+                );
+            }
+
+            for input in ["--", "-1", "+1", "1.5", "1.foo", "1 foo", "a. foo"] {
+                assert_eq!(itemline_parser(input, &markers), Err(Error((input, Tag))));
             }
+            assert!(itemline_parser("- foo", &[ItemMarker::Asterisk]).is_err());
         }
 
         #[test]
@@ -1177,7 +1419,7 @@ mod tests {
             use nom::error::ErrorKind::Tag;
             use nom::Err::Error;
 
-            for (input, expected_rest) in vec![
+            for (input, expected_rest) in [
                 ("   baz   ", "baz   "),
                 ("qux   *", "qux   *"),
                 ("  abc * def", "abc * def"),
@@ -1185,7 +1427,54 @@ mod tests {
                 (r"  \*  ", r"\*  "),
                 (r"\*  ", r"\*  "),
             ] {
-                assert_eq!(itemline_parser(input), Err(Error((expected_rest, Tag))));
+                assert_eq!(
+                    itemline_parser(input, &[ItemMarker::Asterisk]),
+                    Err(Error((expected_rest, Tag)))
+                );
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod headingline_parser_spec {
+        use super::super::headingline_parser;
+
+        #[test]
+        fn valid_headinglines() {
+            use super::super::RawHeadinglineParseData;
+
+            for (input, indent_depth, level, starred, title, label, comment) in [
+                ("= Foo", 0, 1, false, "Foo", None, ""),
+                ("  ==* Foo bar  ", 2, 2, true, "Foo bar", None, ""),
+                (r"=== 50\% % baz", 0, 3, false, r"50\%", None, "% baz"),
+                ("= Foo {#sec:foo}", 0, 1, false, "Foo", Some("sec:foo"), ""),
+                ("= {#sec:foo}", 0, 1, false, "{#sec:foo}", None, ""),
+                ("= Foo {#}", 0, 1, false, "Foo {#}", None, ""),
+                ("= $a = b$", 0, 1, false, "$a = b$", None, ""),
+            ] {
+                assert_eq!(
+                    headingline_parser(input),
+                    Ok((
+                        "",
+                        RawHeadinglineParseData {
+                            indent_depth,
+                            level,
+                            starred,
+                            title: title.to_string(),
+                            label: label.map(|l| l.to_string()),
+                            comment: comment.to_string(),
+                        }
+                    ))
+                );
+            }
+        }
+
+        #[test]
+        fn not_headinglines() {
+            for input in [
+                "", "=", "==  ", "==foo", "=*foo", "a = b", "= % foo", r"\= foo",
+            ] {
+                assert!(headingline_parser(input).is_err(), "{}", input);
             }
         }
     }
@@ -1196,9 +1485,9 @@ mod tests {
 
         #[test]
         fn yield_single_line_command() {
-            use super::super::Hashline::PlainLine;
+            use super::super::Hashline::Command;
 
-            for is_a_list_environment in vec![true, false] {
+            for is_a_list_environment in [true, false] {
                 for (input, expected_result) in vec![
                     (" # foo: bar", r" \foo{bar}"),
                     ("  # foo{qux}: bar", r"  \foo{qux}{bar}"),
@@ -1211,11 +1500,53 @@ mod tests {
                     (r"  # foo[\:qux]: bar   % baz", r"  \foo[:qux]{bar} % baz"),
                     (r"  # foo: bar \% % baz", r"  \foo{bar \%} % baz"),
                     (r"  # foo: bar \%% baz", r"  \foo{bar \%} % baz"),
+                    ("# foo: : bar", r"\foo{: bar}"),
+                    (
+                        "# href: https://x.org | the site",
+                        r"\href{https://x.org}{the site}",
+                    ),
+                    (r"# frac: 1 | 2 \| 3", r"\frac{1}{2 \| 3}"),
+                    ("# abs: $|x|$", r"\abs{$|x|$}"),
+                    ("# foo[bar]: | baz % qux", r"\foo[bar]{}{baz} % qux"),
                 ] {
-                    assert_eq!(
-                        process_line(input.to_string(), is_a_list_environment),
-                        PlainLine(expected_result.to_string())
-                    );
+                    match process_line(
+                        input.to_string(),
+                        is_a_list_environment,
+                        true,
+                        &Default::default(),
+                    ) {
+                        Command(c) => assert_eq!(c.latex(), expected_result),
+                        h => panic!("not a command: {:?}", h),
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn yield_inline_environment() {
+            use super::super::Hashline::InlineEnv;
+
+            for is_a_list_environment in [true, false] {
+                for (input, expected_result) in [
+                    (
+                        "# equation:: E = mc^2",
+                        r"\begin{equation} E = mc^2 \end{equation}",
+                    ),
+                    (
+                        "  # center[qux]::foo   % bar",
+                        r"  \begin{center}[qux] foo \end{center} % bar",
+                    ),
+                    ("# foo::", r"\begin{foo} \end{foo}"),
+                ] {
+                    match process_line(
+                        input.to_string(),
+                        is_a_list_environment,
+                        true,
+                        &Default::default(),
+                    ) {
+                        InlineEnv(e) => assert_eq!(e.latex(), expected_result),
+                        h => panic!("not an inline environment: {:?}", h),
+                    }
                 }
             }
         }
@@ -1238,15 +1569,37 @@ mod tests {
                 " * foo",
                 r"  \\",
             ] {
-                assert_eq!(process_line(s.to_string(), false), PlainLine(s.to_string()));
+                assert_eq!(
+                    process_line(s.to_string(), false, true, &Default::default()),
+                    PlainLine(s.to_string())
+                );
             }
         }
 
+        #[test]
+        fn yield_headingline_only_if_headings_are_active() {
+            use super::super::Hashline::{Heading, PlainLine};
+            use crate::{Headings, TranspileOptions};
+
+            let options = TranspileOptions {
+                headings: Headings::Article,
+                ..Default::default()
+            };
+            match process_line("== Foo".to_string(), false, true, &options) {
+                Heading(c, None) => assert_eq!(c.latex(), r"\subsection{Foo}"),
+                h => panic!("not a heading: {:?}", h),
+            }
+            assert_eq!(
+                process_line("  = y".to_string(), false, false, &options),
+                PlainLine("  = y".to_string())
+            );
+        }
+
         #[test]
         fn yield_itemline() {
-            use super::super::Hashline::PlainLine;
+            use super::super::Hashline::Item;
 
-            for (input, expected_result) in vec![
+            for (input, expected_result) in [
                 ("* foo bar 123", r"\item foo bar 123"),
                 ("  * 123 foo bar", r"  \item 123 foo bar"),
                 (r"   * $\frac{1}{2}", r"   \item $\frac{1}{2}"),
@@ -1254,10 +1607,10 @@ mod tests {
                 ("  *", r"  \item"),
                 ("  *[A] B", r"  \item [A] B"),
             ] {
-                assert_eq!(
-                    process_line(input.to_string(), true),
-                    PlainLine(expected_result.to_string())
-                );
+                match process_line(input.to_string(), true, false, &Default::default()) {
+                    Item(i) => assert_eq!(i.latex(), expected_result),
+                    h => panic!("not an item: {:?}", h),
+                }
             }
         }
 
@@ -1265,7 +1618,7 @@ mod tests {
         fn yield_plainline_in_a_list_environment() {
             use super::super::Hashline::PlainLine;
 
-            for s in vec![
+            for s in [
                 r"\item foo bar 123",
                 "   123 foo bar",
                 r"    $\frac{1}{2}",
@@ -1274,7 +1627,10 @@ mod tests {
                 "  ",
                 "  [A] B",
             ] {
-                assert_eq!(process_line(s.to_string(), true), PlainLine(s.to_string()));
+                assert_eq!(
+                    process_line(s.to_string(), true, false, &Default::default()),
+                    PlainLine(s.to_string())
+                );
             }
         }
 
@@ -1283,8 +1639,8 @@ mod tests {
             use super::super::Hashline::OpenEnv;
             use crate::parsing_types::Environment;
 
-            for is_a_list_environment in vec![true, false] {
-                for (input, expected_result) in vec![
+            for is_a_list_environment in [true, false] {
+                for (input, expected_result) in [
                     (
                         " # foo: ",
                         Environment::new(
@@ -1357,7 +1713,12 @@ mod tests {
                     ),
                 ] {
                     assert_eq!(
-                        process_line(input.to_string(), is_a_list_environment),
+                        process_line(
+                            input.to_string(),
+                            is_a_list_environment,
+                            true,
+                            &Default::default()
+                        ),
                         OpenEnv(expected_result),
                     );
                 }
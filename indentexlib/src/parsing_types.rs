@@ -1,3 +1,5 @@
+use crate::parsers::split_args;
+
 #[derive(Debug, PartialEq)]
 pub struct RawHashlineParseData {
     pub(super) indent_depth: usize,
@@ -5,21 +7,39 @@ pub struct RawHashlineParseData {
     pub(super) opts: String,
     pub(super) args: String,
     pub(super) comment: String,
+    pub(super) inline: bool,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct RawItemlineParseData {
     pub(super) indent_depth: usize,
+    pub(super) number: Option<usize>,
     pub(super) item: String,
 }
 
+#[derive(Debug, PartialEq)]
+pub struct RawHeadinglineParseData {
+    pub(super) indent_depth: usize,
+    pub(super) level: usize,
+    pub(super) starred: bool,
+    pub(super) title: String,
+    pub(super) label: Option<String>,
+    pub(super) comment: String,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Hashline {
     OpenEnv(Environment),
+    InlineEnv(InlineEnvironment),
+    Command(Command),
+    /// Sectioning command and an optional `\label` for it
+    Heading(Command, Option<Command>),
+    Item(Item),
     PlainLine(String),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Environment {
     indent_depth: usize,
     name: String,
@@ -28,8 +48,46 @@ pub struct Environment {
     is_list_like: bool,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InlineEnvironment {
+    environment: Environment,
+    content: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Command {
+    indent_depth: usize,
+    name: String,
+    opts: String,
+    args: Vec<String>,
+    comment: String,
+}
+
+/// Rules of a pipe table, see `Environment::table_rules`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TableRules {
+    /// `\toprule`, `\midrule` and `\bottomrule`
+    Booktabs,
+    /// `\hline` everywhere
+    Hline,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Item {
+    indent_depth: usize,
+    command: String,
+    /// Number given by the author, e.g. `3` for `3. foo`
+    number: Option<usize>,
+    /// Optional argument of the item command, e.g. `\item[3.] foo`
+    label: Option<String>,
+    content: String,
+}
+
 #[inline]
-fn is_a_list_environment(input: &str) -> bool {
+pub(crate) fn is_a_list_environment(input: &str) -> bool {
     fn parser(input: &str) -> nom::IResult<&str, &str> {
         use nom::branch::alt;
         use nom::bytes::complete::tag;
@@ -39,9 +97,27 @@ fn is_a_list_environment(input: &str) -> bool {
     parser(input.trim_start()).is_ok()
 }
 
+#[inline]
+fn brace_groups(args: &[String]) -> String {
+    args.iter().map(|a| format!("{{{}}}", a)).collect()
+}
+
 impl From<RawHashlineParseData> for Hashline {
     fn from(raw_hashline: RawHashlineParseData) -> Self {
-        if raw_hashline.args.trim().is_empty() {
+        if raw_hashline.inline {
+            // A double colon opens and closes an environment on the same line
+            let is_list_like = is_a_list_environment(raw_hashline.name.as_ref());
+            Hashline::InlineEnv(InlineEnvironment {
+                environment: Environment {
+                    indent_depth: raw_hashline.indent_depth,
+                    name: raw_hashline.name,
+                    opts: raw_hashline.opts,
+                    comment: raw_hashline.comment,
+                    is_list_like,
+                },
+                content: raw_hashline.args,
+            })
+        } else if raw_hashline.args.trim().is_empty() {
             // If no args are given, it's an environment
             let is_list_like = is_a_list_environment(raw_hashline.name.as_ref());
             Hashline::OpenEnv(Environment {
@@ -52,43 +128,70 @@ impl From<RawHashlineParseData> for Hashline {
                 is_list_like,
             })
         } else {
-            // If there are some args, it's a single-line command
-            Hashline::PlainLine(format!(
-                r"{dummy:ind$}\{name}{opts}{{{args}}}{comment_sep}{comment}",
-                dummy = "",
-                ind = raw_hashline.indent_depth,
-                name = raw_hashline.name,
-                opts = raw_hashline.opts,
-                args = raw_hashline.args,
-                comment_sep = if raw_hashline.comment.is_empty() {
-                    ""
-                } else {
-                    " "
-                },
-                comment = raw_hashline.comment.trim(),
-            ))
+            let mut args = split_args(&raw_hashline.args);
+            if args.len() > 1 && args.last().is_some_and(|a| a.is_empty()) {
+                // A trailing separator opens an environment with mandatory args
+                args.pop();
+                let is_list_like = is_a_list_environment(raw_hashline.name.as_ref());
+                Hashline::OpenEnv(Environment {
+                    indent_depth: raw_hashline.indent_depth,
+                    name: raw_hashline.name,
+                    opts: raw_hashline.opts + &brace_groups(&args),
+                    comment: raw_hashline.comment,
+                    is_list_like,
+                })
+            } else {
+                // If there are some args, it's a single-line command
+                Hashline::Command(Command {
+                    indent_depth: raw_hashline.indent_depth,
+                    name: raw_hashline.name,
+                    opts: raw_hashline.opts,
+                    args,
+                    comment: raw_hashline.comment,
+                })
+            }
         }
     }
 }
 
 impl From<RawItemlineParseData> for Hashline {
     fn from(raw_itemline: RawItemlineParseData) -> Self {
-        Hashline::PlainLine(format!(
-            r"{dummy:ind$}\item{item_sep}{content}",
-            dummy = "",
-            ind = raw_itemline.indent_depth,
-            content = raw_itemline.item,
-            item_sep = if raw_itemline.item.is_empty() {
-                ""
-            } else {
-                " "
-            },
-        ))
+        Hashline::Item(Item {
+            indent_depth: raw_itemline.indent_depth,
+            command: "item".to_string(),
+            number: raw_itemline.number,
+            label: None,
+            content: raw_itemline.item,
+        })
+    }
+}
+
+impl RawHeadinglineParseData {
+    /// Turn into a heading with a sectioning command like `section`
+    pub(crate) fn into_hashline(self, name: &str) -> Hashline {
+        let indent_depth = self.indent_depth;
+        let label = self.label.map(|l| {
+            Command::new(
+                indent_depth,
+                "label".to_string(),
+                String::new(),
+                vec![l],
+                String::new(),
+            )
+        });
+        let heading = Command::new(
+            self.indent_depth,
+            format!("{}{}", name, if self.starred { "*" } else { "" }),
+            String::new(),
+            vec![self.title],
+            self.comment,
+        );
+
+        Hashline::Heading(heading, label)
     }
 }
 
 impl Environment {
-    #[cfg(test)]
     pub fn new(
         indent_depth: usize,
         name: String,
@@ -126,66 +229,343 @@ impl Environment {
         )
     }
 
+    pub fn context_begin(&self) -> String {
+        format!(
+            r"{dummy:ind$}\start{name}{opts}{comment_sep}{comment}",
+            name = self.name,
+            opts = self.opts,
+            comment = self.comment,
+            dummy = "",
+            ind = self.indent_depth,
+            comment_sep = if self.comment.is_empty() { "" } else { " " },
+        )
+    }
+
+    pub fn context_end(&self) -> String {
+        format!(
+            r"{dummy:ind$}\stop{name}",
+            name = self.name,
+            dummy = "",
+            ind = self.indent_depth,
+        )
+    }
+
     pub fn indent_depth(&self) -> usize {
         self.indent_depth
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn opts(&self) -> &str {
+        &self.opts
+    }
+
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+
     pub fn is_list_like(&self) -> bool {
         self.is_list_like
     }
+
+    pub(crate) fn set_list_like(&mut self, is_list_like: bool) {
+        self.is_list_like = is_list_like;
+    }
+
+    /// Get the rules if this environment may be a pipe table, i.e. `# table[booktabs]:` or
+    /// `# table[hline]:`; it only is one if its body consists of `| a | b |` rows
+    ///
+    /// A plain `# table:` is a float and never a pipe table.
+    pub fn table_rules(&self) -> Option<TableRules> {
+        match (self.name.as_ref(), self.opts.as_ref()) {
+            ("table", "[booktabs]") => Some(TableRules::Booktabs),
+            ("table", "[hline]") => Some(TableRules::Hline),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn into_tabular(self, column_spec: &str) -> Self {
+        Self {
+            name: "tabular".to_string(),
+            opts: format!("{{{}}}", column_spec),
+            ..self
+        }
+    }
+}
+
+impl InlineEnvironment {
+    pub fn new(environment: Environment, content: String) -> Self {
+        Self {
+            environment,
+            content,
+        }
+    }
+
+    pub fn latex(&self) -> String {
+        let e = &self.environment;
+        format!(
+            r"{dummy:ind$}\begin{{{name}}}{opts}{content_sep}{content} \end{{{name}}}{comment_sep}{comment}",
+            dummy = "",
+            ind = e.indent_depth,
+            name = e.name,
+            opts = e.opts,
+            content = self.content,
+            content_sep = if self.content.is_empty() { "" } else { " " },
+            comment_sep = if e.comment.is_empty() { "" } else { " " },
+            comment = e.comment,
+        )
+    }
+
+    pub fn context(&self) -> String {
+        let e = &self.environment;
+        format!(
+            r"{dummy:ind$}\start{name}{opts}{content_sep}{content} \stop{name}{comment_sep}{comment}",
+            dummy = "",
+            ind = e.indent_depth,
+            name = e.name,
+            opts = e.opts,
+            content = self.content,
+            content_sep = if self.content.is_empty() { "" } else { " " },
+            comment_sep = if e.comment.is_empty() { "" } else { " " },
+            comment = e.comment,
+        )
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+impl Command {
+    pub fn new(
+        indent_depth: usize,
+        name: String,
+        opts: String,
+        args: Vec<String>,
+        comment: String,
+    ) -> Self {
+        Self {
+            indent_depth,
+            name,
+            opts,
+            args,
+            comment,
+        }
+    }
+
+    pub fn latex(&self) -> String {
+        format!(
+            r"{dummy:ind$}\{name}{opts}{args}{comment_sep}{comment}",
+            dummy = "",
+            ind = self.indent_depth,
+            name = self.name,
+            opts = self.opts,
+            args = brace_groups(&self.args),
+            comment_sep = if self.comment.is_empty() { "" } else { " " },
+            comment = self.comment.trim(),
+        )
+    }
+
+    pub fn indent_depth(&self) -> usize {
+        self.indent_depth
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn opts(&self) -> &str {
+        &self.opts
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn comment(&self) -> &str {
+        &self.comment
+    }
+}
+
+impl Item {
+    pub fn new(indent_depth: usize, content: String) -> Self {
+        Self::with_command(indent_depth, "item".to_string(), content)
+    }
+
+    pub fn with_command(indent_depth: usize, command: String, content: String) -> Self {
+        Self {
+            indent_depth,
+            command,
+            number: None,
+            label: None,
+            content,
+        }
+    }
+
+    pub fn latex(&self) -> String {
+        format!(
+            r"{dummy:ind$}\{command}{label}{item_sep}{content}",
+            dummy = "",
+            ind = self.indent_depth,
+            command = self.command,
+            label = self
+                .label
+                .as_ref()
+                .map_or(String::new(), |l| format!("[{}]", l)),
+            content = self.content,
+            item_sep = if self.content.is_empty() { "" } else { " " },
+        )
+    }
+
+    pub fn indent_depth(&self) -> usize {
+        self.indent_depth
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn number(&self) -> Option<usize> {
+        self.number
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub(crate) fn set_command(&mut self, command: &str) {
+        self.command = command.to_string();
+    }
+
+    pub(crate) fn set_label(&mut self, label: String) {
+        self.label = Some(label);
+    }
 }
 
 // LCOV_EXCL_START
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn raw_headingline_parser_data_into_hashline() {
+        use super::{Hashline, RawHeadinglineParseData};
+
+        match (RawHeadinglineParseData {
+            indent_depth: 2,
+            level: 1,
+            starred: true,
+            title: "Foo".to_string(),
+            label: Some("sec:foo".to_string()),
+            comment: "% bar".to_string(),
+        })
+        .into_hashline("section")
+        {
+            Hashline::Heading(heading, Some(label)) => {
+                assert_eq!(heading.latex(), r"  \section*{Foo} % bar");
+                assert_eq!(label.latex(), r"  \label{sec:foo}");
+            }
+            h => panic!("not a labelled heading: {:?}", h),
+        }
+    }
+
+    #[test]
+    fn table_environments() {
+        use super::{Environment, TableRules};
+
+        let table = |opts: &str| {
+            Environment::new(
+                2,
+                "table".to_string(),
+                opts.to_string(),
+                "% foo".to_string(),
+                false,
+            )
+        };
+        assert_eq!(table("").table_rules(), None);
+        assert_eq!(table("[hline]").table_rules(), Some(TableRules::Hline));
+        assert_eq!(
+            table("[booktabs]").table_rules(),
+            Some(TableRules::Booktabs)
+        );
+        assert_eq!(table("[htbp]").table_rules(), None);
+
+        let tabular = table("[booktabs]").into_tabular("lr");
+        assert_eq!(tabular.latex_begin(), r"  \begin{tabular}{lr} % foo");
+        assert_eq!(tabular.latex_end(), r"  \end{tabular}");
+        assert_eq!(tabular.table_rules(), None);
+    }
+
     #[test]
     fn list_environment_recognition() {
         use super::is_a_list_environment;
 
-        assert_eq!(is_a_list_environment("itemize"), true);
-        assert_eq!(is_a_list_environment("enumerate*"), true);
-        assert_eq!(is_a_list_environment("  description  *"), true);
-        assert_eq!(is_a_list_environment("    descriptionitemize"), true);
-        assert_eq!(is_a_list_environment("item"), false);
-        assert_eq!(is_a_list_environment("   itemiz"), false);
-        assert_eq!(is_a_list_environment("   foobar"), false);
+        assert!(is_a_list_environment("itemize"));
+        assert!(is_a_list_environment("enumerate*"));
+        assert!(is_a_list_environment("  description  *"));
+        assert!(is_a_list_environment("    descriptionitemize"));
+        assert!(!is_a_list_environment("item"));
+        assert!(!is_a_list_environment("   itemiz"));
+        assert!(!is_a_list_environment("   foobar"));
+    }
+
+    fn latex(hashline: super::Hashline) -> String {
+        use super::Hashline::{Command, Item};
+
+        match hashline {
+            Command(c) => c.latex(),
+            Item(i) => i.latex(),
+            h => panic!("neither a command nor an item: {:?}", h),
+        }
     }
 
     #[cfg(test)]
     mod raw_hashline_parser_data_into_hashline {
         use super::super::{Hashline, RawHashlineParseData};
+        use super::latex;
 
         #[test]
-        fn plain_lines() {
+        fn commands() {
             assert_eq!(
-                Hashline::from(RawHashlineParseData {
+                latex(Hashline::from(RawHashlineParseData {
                     indent_depth: 0,
                     name: "foo".to_string(),
                     opts: "".to_string(),
                     args: "bar".to_string(),
-                    comment: "".to_string()
-                }),
-                Hashline::PlainLine("\\foo{bar}".to_string())
+                    comment: "".to_string(),
+                    inline: false,
+                })),
+                "\\foo{bar}"
             );
             assert_eq!(
-                Hashline::from(RawHashlineParseData {
+                latex(Hashline::from(RawHashlineParseData {
                     indent_depth: 2,
                     name: "foo".to_string(),
                     opts: "".to_string(),
                     args: "bar".to_string(),
-                    comment: "qux".to_string()
-                }),
-                Hashline::PlainLine("  \\foo{bar} qux".to_string())
+                    comment: "qux".to_string(),
+                    inline: false,
+                })),
+                "  \\foo{bar} qux"
             );
             assert_eq!(
-                Hashline::from(RawHashlineParseData {
+                latex(Hashline::from(RawHashlineParseData {
                     indent_depth: 4,
                     name: "foo".to_string(),
                     opts: "bar".to_string(),
                     args: "qux".to_string(),
-                    comment: "".to_string()
-                }),
-                Hashline::PlainLine("    \\foobar{qux}".to_string())
+                    comment: "".to_string(),
+                    inline: false,
+                })),
+                "    \\foobar{qux}"
             );
         }
 
@@ -199,7 +579,8 @@ mod tests {
                     name: "foo".to_string(),
                     opts: "bar".to_string(),
                     args: "".to_string(),
-                    comment: "".to_string()
+                    comment: "".to_string(),
+                    inline: false,
                 }),
                 Hashline::OpenEnv(Environment {
                     indent_depth: 0,
@@ -215,7 +596,8 @@ mod tests {
                     name: "foo".to_string(),
                     opts: "".to_string(),
                     args: "".to_string(),
-                    comment: "bar".to_string()
+                    comment: "bar".to_string(),
+                    inline: false,
                 }),
                 Hashline::OpenEnv(Environment {
                     indent_depth: 2,
@@ -231,7 +613,8 @@ mod tests {
                     name: "foo".to_string(),
                     opts: "bar".to_string(),
                     args: "".to_string(),
-                    comment: "qux".to_string()
+                    comment: "qux".to_string(),
+                    inline: false,
                 }),
                 Hashline::OpenEnv(Environment {
                     indent_depth: 4,
@@ -247,7 +630,8 @@ mod tests {
                     name: "itemize".to_string(),
                     opts: "bar".to_string(),
                     args: "".to_string(),
-                    comment: "qux".to_string()
+                    comment: "qux".to_string(),
+                    inline: false,
                 }),
                 Hashline::OpenEnv(Environment {
                     indent_depth: 0,
@@ -265,46 +649,52 @@ mod tests {
         use super::{Hashline, RawItemlineParseData};
 
         assert_eq!(
-            Hashline::from(RawItemlineParseData {
+            latex(Hashline::from(RawItemlineParseData {
+                number: None,
                 indent_depth: 0,
                 item: "".to_string()
-            }),
-            Hashline::PlainLine(r"\item".to_string())
+            })),
+            r"\item"
         );
         assert_eq!(
-            Hashline::from(RawItemlineParseData {
+            latex(Hashline::from(RawItemlineParseData {
+                number: None,
                 indent_depth: 0,
                 item: "".to_string()
-            }),
-            Hashline::PlainLine(r"\item".to_string())
+            })),
+            r"\item"
         );
         assert_eq!(
-            Hashline::from(RawItemlineParseData {
+            latex(Hashline::from(RawItemlineParseData {
+                number: None,
                 indent_depth: 2,
                 item: "".to_string()
-            }),
-            Hashline::PlainLine(r"  \item".to_string())
+            })),
+            r"  \item"
         );
         assert_eq!(
-            Hashline::from(RawItemlineParseData {
+            latex(Hashline::from(RawItemlineParseData {
+                number: None,
                 indent_depth: 0,
                 item: "foo".to_string()
-            }),
-            Hashline::PlainLine(r"\item foo".to_string())
+            })),
+            r"\item foo"
         );
         assert_eq!(
-            Hashline::from(RawItemlineParseData {
+            latex(Hashline::from(RawItemlineParseData {
+                number: None,
                 indent_depth: 3,
                 item: "bar".to_string()
-            }),
-            Hashline::PlainLine(r"   \item bar".to_string())
+            })),
+            r"   \item bar"
         );
         assert_eq!(
-            Hashline::from(RawItemlineParseData {
+            latex(Hashline::from(RawItemlineParseData {
+                number: None,
                 indent_depth: 0,
                 item: "**".to_string()
-            }),
-            Hashline::PlainLine(r"\item **".to_string())
+            })),
+            r"\item **"
         );
     }
 
@@ -322,8 +712,13 @@ mod tests {
 
         assert_eq!(env_1.latex_begin(), "\\begin{foo}bar % baz");
         assert_eq!(env_1.latex_end(), "\\end{foo}");
-        assert_eq!(env_1.is_list_like(), true);
+        assert_eq!(env_1.context_begin(), "\\startfoobar % baz");
+        assert_eq!(env_1.context_end(), "\\stopfoo");
+        assert!(env_1.is_list_like());
         assert_eq!(env_1.indent_depth(), 0);
+        assert_eq!(env_1.name(), "foo");
+        assert_eq!(env_1.opts(), "bar");
+        assert_eq!(env_1.comment(), "% baz");
 
         let env_2 = Environment {
             indent_depth: 2,
@@ -335,8 +730,69 @@ mod tests {
 
         assert_eq!(env_2.latex_begin(), "  \\begin{abc}def");
         assert_eq!(env_2.latex_end(), "  \\end{abc}");
-        assert_eq!(env_2.is_list_like(), false);
+        assert_eq!(env_2.context_begin(), "  \\startabcdef");
+        assert_eq!(env_2.context_end(), "  \\stopabc");
+        assert!(!env_2.is_list_like());
         assert_eq!(env_2.indent_depth(), 2);
     }
+
+    #[test]
+    fn command_and_item_methods() {
+        use super::{Command, Item};
+
+        let cmd = Command::new(
+            2,
+            "foo".to_string(),
+            "[bar]".to_string(),
+            vec!["baz".to_string(), "quux".to_string()],
+            "% qux".to_string(),
+        );
+        assert_eq!(cmd.latex(), "  \\foo[bar]{baz}{quux} % qux");
+        assert_eq!(cmd.indent_depth(), 2);
+        assert_eq!(cmd.name(), "foo");
+        assert_eq!(cmd.opts(), "[bar]");
+        assert_eq!(cmd.args(), ["baz", "quux"]);
+        assert_eq!(cmd.comment(), "% qux");
+
+        let item = Item::new(1, "foo".to_string());
+        assert_eq!(item.latex(), " \\item foo");
+        assert_eq!(item.indent_depth(), 1);
+        assert_eq!(item.command(), "item");
+        assert_eq!(item.content(), "foo");
+
+        let mut numbered = Item::new(2, "bar".to_string());
+        numbered.set_label("3.".to_string());
+        assert_eq!(numbered.latex(), "  \\item[3.] bar");
+        assert_eq!(numbered.label(), Some("3."));
+        assert_eq!(numbered.number(), None);
+
+        let bibitem = Item::with_command(0, "bibitem".to_string(), "{foo} bar".to_string());
+        assert_eq!(bibitem.latex(), "\\bibitem {foo} bar");
+        assert_eq!(bibitem.command(), "bibitem");
+    }
+
+    #[test]
+    fn inline_environment_methods() {
+        use super::{Environment, InlineEnvironment};
+
+        let env = Environment::new(
+            2,
+            "foo".to_string(),
+            "[bar]".to_string(),
+            "% qux".to_string(),
+            false,
+        );
+        let inline = InlineEnvironment::new(env.clone(), "baz".to_string());
+        assert_eq!(inline.latex(), "  \\begin{foo}[bar] baz \\end{foo} % qux");
+        assert_eq!(inline.context(), "  \\startfoo[bar] baz \\stopfoo % qux");
+        assert_eq!(inline.environment(), &env);
+        assert_eq!(inline.content(), "baz");
+
+        let empty = InlineEnvironment::new(
+            Environment::new(0, "foo".to_string(), "".to_string(), "".to_string(), false),
+            "".to_string(),
+        );
+        assert_eq!(empty.latex(), "\\begin{foo} \\end{foo}");
+    }
 }
 // LCOV_EXCL_STOP
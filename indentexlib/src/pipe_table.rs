@@ -0,0 +1,198 @@
+use crate::document::{Environment, Node, NodeKind, TableRules};
+use std::ops::Range;
+
+/// Body line of a pipe table
+#[derive(Debug, PartialEq)]
+enum PipeRow {
+    /// `| a | b |`, cells are trimmed, `\|` does not separate them and `&` is escaped
+    Cells(Vec<String>),
+    /// `|:---|---:|`, with the alignment of each column
+    Separator(Vec<char>),
+    /// Empty or comment-only line
+    Skip,
+}
+
+/// Split a row at every `|` but the one of the LaTeX command `\|` and escape every `&`, which
+/// would separate the cells of the `tabular` otherwise
+fn split_cells(inner: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::with_capacity(inner.len());
+    let mut escaped = false;
+    for c in inner.chars() {
+        match c {
+            '|' if !escaped => {
+                cells.push(cell.trim().to_string());
+                cell.clear();
+            }
+            '&' if !escaped => cell.push_str(r"\&"),
+            _ => cell.push(c),
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    cells.push(cell.trim().to_string());
+
+    cells
+}
+
+fn parse_row(line: &str) -> Option<PipeRow> {
+    let code = crate::utils::split_comment(line).0.trim();
+    if code.is_empty() {
+        return Some(PipeRow::Skip);
+    }
+    let inner = code.strip_prefix('|')?.strip_suffix('|')?;
+    if inner.ends_with('\\') {
+        // The closing pipe is escaped
+        return None;
+    }
+
+    let cells = split_cells(inner);
+    let is_separator = cells.iter().all(|c| {
+        let dashes = c.trim_start_matches(':').trim_end_matches(':');
+        !dashes.is_empty() && dashes.chars().all(|d| d == '-')
+    });
+    if is_separator {
+        let alignments = cells
+            .iter()
+            .map(|c| match (c.starts_with(':'), c.ends_with(':')) {
+                (true, true) => 'c',
+                (false, true) => 'r',
+                _ => 'l',
+            })
+            .collect();
+        Some(PipeRow::Separator(alignments))
+    } else {
+        Some(PipeRow::Cells(cells))
+    }
+}
+
+/// Rewrite a pipe table into a `tabular` or return it unchanged if it is none
+///
+/// Every source line stays a single output line, the top and bottom rules are attributed
+/// to the opening line and to the last row.
+pub fn rewrite(
+    environment: Environment,
+    children: Vec<Node>,
+    span: &Range<usize>,
+) -> (Environment, Vec<Node>) {
+    let rules = match environment.table_rules() {
+        Some(rules) => rules,
+        None => return (environment, children),
+    };
+    let rows = children
+        .iter()
+        .map(|n| match &n.kind {
+            NodeKind::Text(l) => parse_row(l),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+    let rows = match rows {
+        Some(rows) if rows.iter().any(|r| *r != PipeRow::Skip) => rows,
+        _ => return (environment, children),
+    };
+
+    let (top, mid, bottom) = match rules {
+        TableRules::Booktabs => (r"\toprule", r"\midrule", r"\bottomrule"),
+        TableRules::Hline => (r"\hline", r"\hline", r"\hline"),
+    };
+    let columns = rows
+        .iter()
+        .map(|r| match r {
+            PipeRow::Cells(c) => c.len(),
+            PipeRow::Separator(a) => a.len(),
+            PipeRow::Skip => 0,
+        })
+        .max()
+        .unwrap_or(0);
+    let mut column_spec = rows
+        .iter()
+        .find_map(|r| match r {
+            PipeRow::Separator(a) => Some(a.iter().collect::<String>()),
+            _ => None,
+        })
+        .unwrap_or_default();
+    while column_spec.len() < columns {
+        column_spec.push('l');
+    }
+
+    let row_indent = children
+        .iter()
+        .zip(rows.iter())
+        .find_map(|(n, r)| match (&n.kind, r) {
+            (NodeKind::Text(l), PipeRow::Cells(_)) | (NodeKind::Text(l), PipeRow::Separator(_)) => {
+                Some(l.len() - l.trim_start().len())
+            }
+            _ => None,
+        })
+        .unwrap_or(environment.indent_depth() + 2);
+    let rule = |r: &str, span: Range<usize>| {
+        Node::new(
+            NodeKind::Text(format!("{:ind$}{}", "", r, ind = row_indent)),
+            span,
+        )
+    };
+
+    let mut body = Vec::with_capacity(children.len() + 2);
+    body.push(rule(top, span.start..span.start + 1));
+    let mut last_span = span.start..span.start + 1;
+    for (node, row) in children.into_iter().zip(rows) {
+        let line = match (&node.kind, &row) {
+            (NodeKind::Text(l), PipeRow::Cells(_)) | (NodeKind::Text(l), PipeRow::Separator(_)) => {
+                l
+            }
+            // Blank and comment lines are passed through
+            _ => {
+                body.push(node);
+                continue;
+            }
+        };
+        let indent = line.len() - line.trim_start().len();
+        let comment = crate::utils::split_comment(line).1;
+        let comment_sep = if comment.is_empty() { "" } else { " " };
+        let text = match row {
+            PipeRow::Cells(cells) => format!(
+                r"{:ind$}{} \\{}{}",
+                "",
+                cells.join(" & "),
+                comment_sep,
+                comment,
+                ind = indent
+            ),
+            _ => format!("{:ind$}{}{}{}", "", mid, comment_sep, comment, ind = indent),
+        };
+        last_span = node.span.clone();
+        body.push(Node::new(NodeKind::Text(text), node.span));
+    }
+    body.push(rule(bottom, last_span));
+
+    (environment.into_tabular(&column_spec), body)
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_row() {
+        use super::{parse_row, PipeRow};
+
+        let cells = |c: &[&str]| Some(PipeRow::Cells(c.iter().map(|s| s.to_string()).collect()));
+        for (line, expected) in [
+            ("", Some(PipeRow::Skip)),
+            ("  % foo", Some(PipeRow::Skip)),
+            ("  | a | b |", cells(&["a", "b"])),
+            (r"| $x \| y$ |  | c | % foo", cells(&[r"$x \| y$", "", "c"])),
+            (
+                "|---|:--|--:|:-:|",
+                Some(PipeRow::Separator(vec!['l', 'l', 'r', 'c'])),
+            ),
+            ("| - | b |", cells(&["-", "b"])),
+            (r"| a & b | c \& d |", cells(&[r"a \& b", r"c \& d"])),
+            ("| a | b", None),
+            (r"| a | b \|", None),
+            ("a | b |", None),
+            (r"\centering", None),
+        ] {
+            assert_eq!(parse_row(line), expected);
+        }
+    }
+}
+// LCOV_EXCL_STOP
@@ -0,0 +1,130 @@
+use crate::TranspileOptions;
+
+/// How `parse_lines` treats a physical source line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Region {
+    /// First line of a logical line which is transpiled
+    Transpiled,
+    /// Line which is joined into the hashline above it by a trailing backslash
+    Continuation,
+    /// Body line of a verbatim environment or a line where transpilation is switched off,
+    /// it is passed through as it is
+    Passthrough,
+}
+
+/// Find out how every line is treated, so that preprocessing leaves the passthrough ones alone
+///
+/// This follows `parse_lines`, but tracks only the environments which are verbatim.
+pub fn scan(lines: &[String], options: &TranspileOptions) -> Vec<Region> {
+    use crate::parsers::process_line;
+    use crate::parsing_types::Hashline::{OpenEnv, PlainLine};
+    use crate::utils::{join_continuation_lines, raw_verbatim_end_marker, transpilation_pragma};
+
+    let adjusted_indents = crate::utils::scan_indents(lines);
+    let mut regions = Vec::with_capacity(lines.len());
+
+    // Indentation of the verbatim environment opened by a hashline which is currently open
+    let mut verbatim_indent: Option<usize> = None;
+    // End marker of a raw verbatim environment which is currently open
+    let mut raw_verbatim_end: Option<String> = None;
+    // Whether transpilation has been switched off by a pragma
+    let mut transpilation_off = false;
+
+    while regions.len() < lines.len() {
+        let line_num = regions.len();
+        let line = &lines[line_num];
+        let mut end = line_num + 1;
+
+        if transpilation_off {
+            if transpilation_pragma(line) == Some(true) {
+                transpilation_off = false;
+            }
+            regions.push(Region::Passthrough);
+        } else if verbatim_indent.is_some() || raw_verbatim_end.is_some() {
+            if raw_verbatim_end
+                .as_ref()
+                .is_some_and(|marker| line.contains(marker.as_str()))
+            {
+                raw_verbatim_end = None;
+            }
+            regions.push(Region::Passthrough);
+        } else {
+            let (logical, joined) = join_continuation_lines(line.clone(), lines[end..].iter());
+            end += joined;
+            regions.push(Region::Transpiled);
+            regions.extend(std::iter::repeat_n(Region::Continuation, joined));
+
+            transpilation_off = transpilation_pragma(&logical) == Some(false);
+            match process_line(logical, false, false, options) {
+                PlainLine(l) => {
+                    raw_verbatim_end = raw_verbatim_end_marker(&l, &options.verbatim_environments)
+                }
+                OpenEnv(e) if options.verbatim_environments.iter().any(|v| v == e.name()) => {
+                    verbatim_indent = Some(e.indent_depth())
+                }
+                _ => (),
+            }
+        }
+
+        // Like in `parse_lines`, nothing is closed in raw verbatim environments and regions
+        // which are not transpiled
+        if raw_verbatim_end.is_none()
+            && !transpilation_off
+            && verbatim_indent.is_some_and(|i| i >= adjusted_indents[end])
+        {
+            verbatim_indent = None;
+        }
+    }
+
+    regions
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn scan() {
+        use super::scan;
+        use super::Region::{Continuation, Passthrough, Transpiled};
+
+        let input = [
+            "# document:",
+            "  # lstlisting:",
+            "    # ?if debug:",
+            "",
+            "  x",
+            r"  \begin{verbatim}",
+            "{{x}}",
+            r"\end{verbatim}",
+            r"  # foo: a \",
+            "b",
+            "% indentex: off",
+            "  # verbatim:",
+            "% indentex: on",
+            "# verbatim:",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+        assert_eq!(
+            scan(&input, &Default::default()),
+            [
+                Transpiled,
+                Transpiled,
+                Passthrough,
+                Transpiled,
+                Transpiled,
+                Transpiled,
+                Passthrough,
+                Passthrough,
+                Transpiled,
+                Continuation,
+                Transpiled,
+                Passthrough,
+                Passthrough,
+                Transpiled,
+            ]
+        );
+    }
+}
+// LCOV_EXCL_STOP
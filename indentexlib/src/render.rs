@@ -0,0 +1,109 @@
+use crate::backend::Backend;
+use crate::document::{Node, NodeKind};
+use crate::source_map::SourceMap;
+use crate::visitor::{Rewrite, Visitor};
+
+/// Writes LaTeX lines and keeps track of where each one came from
+pub struct Emitter<'a> {
+    text: String,
+    source_map: SourceMap,
+    backend: Backend,
+    visitor: &'a mut dyn Visitor,
+}
+
+impl<'a> Emitter<'a> {
+    pub fn with_capacity(capacity: usize, backend: Backend, visitor: &'a mut dyn Visitor) -> Self {
+        Self {
+            text: String::with_capacity(capacity),
+            source_map: SourceMap::default(),
+            backend,
+            visitor,
+        }
+    }
+
+    pub fn emit_line(&mut self, line: &str, source_line: Option<usize>) {
+        self.text.push_str(line);
+        self.text.push_str(crate::LINESEP);
+        self.source_map.push(source_line);
+    }
+
+    fn emit_rewrite<T, F>(
+        &mut self,
+        rewrite: &Rewrite<T>,
+        original: &T,
+        latex: F,
+        source_line: Option<usize>,
+    ) where
+        F: Fn(&T) -> String,
+    {
+        match rewrite {
+            Rewrite::Keep => self.emit_line(&latex(original), source_line),
+            Rewrite::Modify(modified) => self.emit_line(&latex(modified), source_line),
+            Rewrite::Replace(lines) => {
+                for l in lines {
+                    self.emit_line(l, source_line);
+                }
+            }
+            Rewrite::Drop => {}
+        }
+    }
+
+    pub fn emit_nodes(&mut self, nodes: &[Node]) {
+        use std::borrow::Cow;
+
+        let backend = self.backend;
+        for node in nodes {
+            // Closing tags are attributed to the line which opened the environment
+            let source_line = Some(node.span.start);
+            match node.kind {
+                NodeKind::Environment {
+                    ref environment,
+                    ref children,
+                } => {
+                    let begin = self.visitor.environment_begin(environment);
+                    self.emit_rewrite(
+                        &begin,
+                        environment,
+                        |e| backend.environment_begin(e),
+                        source_line,
+                    );
+                    let environment = match begin {
+                        Rewrite::Modify(e) => Cow::Owned(e),
+                        _ => Cow::Borrowed(environment),
+                    };
+
+                    self.emit_nodes(children);
+
+                    let end = self.visitor.environment_end(&environment);
+                    self.emit_rewrite(
+                        &end,
+                        &environment,
+                        |e| backend.environment_end(e),
+                        source_line,
+                    );
+                }
+                NodeKind::InlineEnvironment(ref e) => {
+                    let rewrite = self.visitor.inline_environment(e);
+                    self.emit_rewrite(&rewrite, e, |e| backend.inline_environment(e), source_line);
+                }
+                NodeKind::Command(ref c) => {
+                    let rewrite = self.visitor.command(c);
+                    self.emit_rewrite(&rewrite, c, |c| backend.command(c), source_line);
+                }
+                NodeKind::Item(ref i) => {
+                    let rewrite = self.visitor.item(i);
+                    self.emit_rewrite(&rewrite, i, |i| backend.item(i), source_line);
+                }
+                NodeKind::Text(ref t) => match self.visitor.text(t) {
+                    // Avoid copying plain lines, since they are the most common ones
+                    Rewrite::Keep => self.emit_line(t, source_line),
+                    rewrite => self.emit_rewrite(&rewrite, t, |t| t.clone(), source_line),
+                },
+            }
+        }
+    }
+
+    pub fn finish(self) -> (String, SourceMap) {
+        (self.text, self.source_map)
+    }
+}
@@ -0,0 +1,97 @@
+/// Maps every line of the transpiled output to the source line it was generated from
+///
+/// All line numbers are zero-based. Output lines without a source line (e.g. the 'DO NOT EDIT'
+/// notice) are mapped to `None`; `\end{...}` lines are mapped to the line opening the environment.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceMap {
+    lines: Vec<Option<usize>>,
+}
+
+impl SourceMap {
+    pub(crate) fn push(&mut self, source_line: Option<usize>) {
+        self.lines.push(source_line);
+    }
+
+    pub fn source_line(&self, output_line: usize) -> Option<usize> {
+        self.lines.get(output_line).cloned().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Option<usize>> + '_ {
+        self.lines.iter().cloned()
+    }
+
+    /// Serialise into the sidecar format
+    ///
+    /// The n-th line of the sidecar contains the one-based source line of the n-th output line,
+    /// or zero if the output line has no source line.
+    pub fn to_sidecar(&self) -> String {
+        let mut s = String::with_capacity(4 * self.lines.len());
+        for l in self.lines.iter() {
+            s.push_str(&l.map_or(0, |n| n + 1).to_string());
+            s.push('\n');
+        }
+        s
+    }
+
+    /// Deserialise from the sidecar format, returns `None` if the input is malformed
+    pub fn from_sidecar(sidecar: &str) -> Option<Self> {
+        sidecar
+            .lines()
+            .map(|l| l.trim().parse::<usize>().ok().map(|n| n.checked_sub(1)))
+            .collect::<Option<Vec<_>>>()
+            .map(|lines| Self { lines })
+    }
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    use super::SourceMap;
+
+    #[test]
+    fn lookup() {
+        let mut sm = SourceMap::default();
+        assert!(sm.is_empty());
+        sm.push(None);
+        sm.push(Some(0));
+        sm.push(Some(3));
+
+        assert_eq!(sm.len(), 3);
+        assert_eq!(sm.source_line(0), None);
+        assert_eq!(sm.source_line(1), Some(0));
+        assert_eq!(sm.source_line(2), Some(3));
+        assert_eq!(sm.source_line(3), None);
+        assert_eq!(sm.iter().collect::<Vec<_>>(), vec![None, Some(0), Some(3)]);
+    }
+
+    #[test]
+    fn to_sidecar() {
+        let mut sm = SourceMap::default();
+        assert_eq!(sm.to_sidecar(), "");
+        sm.push(None);
+        sm.push(Some(0));
+        sm.push(Some(41));
+        assert_eq!(sm.to_sidecar(), "0\n1\n42\n");
+    }
+
+    #[test]
+    fn from_sidecar() {
+        assert_eq!(SourceMap::from_sidecar(""), Some(SourceMap::default()));
+
+        let sm = SourceMap::from_sidecar("0\n1\n42\n").unwrap();
+        assert_eq!(sm.iter().collect::<Vec<_>>(), vec![None, Some(0), Some(41)]);
+        assert_eq!(SourceMap::from_sidecar(&sm.to_sidecar()), Some(sm));
+
+        assert_eq!(SourceMap::from_sidecar("1\nfoo\n"), None);
+        assert_eq!(SourceMap::from_sidecar("-1\n"), None);
+    }
+}
+// LCOV_EXCL_STOP
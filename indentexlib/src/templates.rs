@@ -0,0 +1,277 @@
+use crate::regions::Region;
+
+/// Multi-line snippet which replaces every hashline with its name
+///
+/// Template lines may contain the placeholders `#opts` for the options of the hashline and
+/// `#1` to `#9` for its arguments; use `##` for a literal `#`. Every line is indented like the
+/// hashline which is expanded and may itself be indentex.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    pub name: String,
+    pub lines: Vec<String>,
+}
+
+impl Template {
+    /// Create a template from a body like the one in a configuration file
+    pub fn new(name: String, body: &str) -> Self {
+        Self {
+            name,
+            lines: body.lines().map(|l| l.trim_end().to_string()).collect(),
+        }
+    }
+
+    fn expand(
+        &self,
+        indent_depth: usize,
+        opts: &str,
+        args: &[String],
+        comment: &str,
+    ) -> Vec<String> {
+        let mut expanded = Vec::with_capacity(self.lines.len());
+        for line in self.lines.iter() {
+            let mut l = String::with_capacity(indent_depth + line.len());
+            if !line.is_empty() {
+                l.push_str(&" ".repeat(indent_depth));
+            }
+            let mut rest = line.as_str();
+            while let Some(i) = rest.find('#') {
+                l.push_str(&rest[..i]);
+                let after = &rest[i + 1..];
+                let digit = after.chars().next().and_then(|c| c.to_digit(10));
+                rest = if let Some(r) = after.strip_prefix('#') {
+                    l.push('#');
+                    r
+                } else if let Some(r) = after.strip_prefix("opts") {
+                    l.push_str(opts);
+                    r
+                } else if let Some(n) = digit {
+                    // Missing arguments are empty
+                    if let Some(a) = (n as usize).checked_sub(1).and_then(|i| args.get(i)) {
+                        l.push_str(a);
+                    }
+                    &after[1..]
+                } else {
+                    l.push('#');
+                    after
+                };
+            }
+            l.push_str(rest);
+            expanded.push(l);
+        }
+
+        if !comment.is_empty() {
+            if let Some(first) = expanded.first_mut() {
+                first.push(' ');
+                first.push_str(comment);
+            }
+        }
+        expanded
+    }
+}
+
+/// Read templates from an indentex file, where each one is defined by an indented body under
+/// a `# template: name` hashline; everything else is ignored
+pub fn parse_templates(lines: &[String]) -> Vec<Template> {
+    use crate::parsers::hashline_parser;
+
+    fn finish(name: String, mut body: Vec<&str>) -> Template {
+        while body.last().is_some_and(|l| l.is_empty()) {
+            body.pop();
+        }
+        let dedent = body
+            .iter()
+            .filter(|l| !l.is_empty())
+            .map(|l| l.len() - l.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let lines = body
+            .iter()
+            .map(|l| l.get(dedent..).unwrap_or("").to_string())
+            .collect();
+
+        Template { name, lines }
+    }
+
+    let mut templates = Vec::new();
+    let mut body: Option<(String, Vec<&str>)> = None;
+    for line in lines.iter() {
+        match body {
+            Some((_, ref mut b)) if line.is_empty() || line.starts_with(' ') => b.push(line),
+            _ => {
+                if let Some((name, b)) = body.take() {
+                    templates.push(finish(name, b));
+                }
+                if let Ok((_, r)) = hashline_parser(line) {
+                    if r.name == "template" && r.indent_depth == 0 && !r.args.is_empty() {
+                        body = Some((r.args, Vec::new()));
+                    }
+                }
+            }
+        }
+    }
+    if let Some((name, b)) = body {
+        templates.push(finish(name, b));
+    }
+
+    templates
+}
+
+/// Expand all template hashlines, together with the index of the source line of every line
+///
+/// Templates are not expanded recursively and neither in verbatim bodies nor where
+/// transpilation is switched off.
+pub(crate) fn expand(
+    lines: Vec<String>,
+    regions: &[Region],
+    templates: &[Template],
+) -> (Vec<String>, Vec<usize>) {
+    use crate::parsers::{hashline_parser, split_args};
+    use crate::utils::join_continuation_lines;
+
+    let mut expanded = Vec::with_capacity(lines.len());
+    let mut origins = Vec::with_capacity(lines.len());
+    let mut line_num = 0;
+    while line_num < lines.len() {
+        // Continuation lines are joined into the hashline above them
+        let joined = regions[line_num + 1..]
+            .iter()
+            .take_while(|&&r| r == Region::Continuation)
+            .count();
+        let end = line_num + 1 + joined;
+
+        let template = if regions[line_num] == Region::Transpiled {
+            let next_lines = lines[line_num + 1..end].iter();
+            let (logical, _) = join_continuation_lines(lines[line_num].clone(), next_lines);
+            hashline_parser(&logical).ok().and_then(|(_, r)| {
+                templates
+                    .iter()
+                    .find(|t| t.name == r.name && !r.inline)
+                    .map(|t| (t, r))
+            })
+        } else {
+            None
+        };
+        match template {
+            Some((t, r)) => {
+                let args = split_args(&r.args);
+                for l in t.expand(r.indent_depth, &r.opts, &args, &r.comment) {
+                    expanded.push(l);
+                    origins.push(line_num);
+                }
+            }
+            None => {
+                expanded.extend_from_slice(&lines[line_num..end]);
+                origins.extend(line_num..end);
+            }
+        }
+        line_num = end;
+    }
+
+    (expanded, origins)
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    use super::Template;
+
+    fn lines(l: &[&str]) -> Vec<String> {
+        l.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn expand_template() {
+        let t = Template::new(
+            "fig".to_string(),
+            "# figure[htbp]:\n  \\includegraphics#opts{#1}\n\n  \\caption{#2} % ##3\n  \\label{#3}\n",
+        );
+        let args = lines(&["img/plot.pdf", "Caption"]);
+        assert_eq!(
+            t.expand(2, "[width=1cm]", &args, "% foo"),
+            lines(&[
+                "  # figure[htbp]: % foo",
+                r"    \includegraphics[width=1cm]{img/plot.pdf}",
+                "",
+                r"    \caption{Caption} % #3",
+                r"    \label{}",
+            ])
+        );
+        assert_eq!(t.expand(0, "", &[], "")[1], r"  \includegraphics{}");
+
+        let t = Template::new("foo".to_string(), "#optsx #option #0 #a #");
+        assert_eq!(t.expand(0, "[x]", &[], ""), lines(&["[x]x #option  #a #"]));
+    }
+
+    #[test]
+    fn parse_templates() {
+        use super::parse_templates;
+
+        let input = lines(&[
+            "% Templates for papers",
+            "# template: fig",
+            "  # figure:",
+            "    \\includegraphics{#1}",
+            "",
+            "# template: eq",
+            "",
+            "    # equation::#1",
+            "",
+            "# section: not a template",
+            "  ignored",
+        ]);
+        assert_eq!(
+            parse_templates(&input),
+            vec![
+                Template {
+                    name: "fig".to_string(),
+                    lines: lines(&["# figure:", "  \\includegraphics{#1}"]),
+                },
+                Template {
+                    name: "eq".to_string(),
+                    lines: lines(&["", "# equation::#1"]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn expand() {
+        use super::expand;
+        use crate::regions::scan;
+
+        let templates = vec![Template::new("two".to_string(), "a{#1}\nb{#2}")];
+        let input = lines(&[
+            "foo",
+            "  # two: x | \\",
+            "    y % bar",
+            "# two:: z",
+            "# three: w",
+            "# two: v",
+            "# verbatim:",
+            "  # two: u",
+            "% indentex: off",
+            "# two: t",
+        ]);
+        let regions = scan(&input, &Default::default());
+        assert_eq!(
+            expand(input, &regions, &templates),
+            (
+                lines(&[
+                    "foo",
+                    "  a{x} % bar",
+                    "  b{y}",
+                    "# two:: z",
+                    "# three: w",
+                    "a{v}",
+                    "b{}",
+                    "# verbatim:",
+                    "  # two: u",
+                    "% indentex: off",
+                    "# two: t",
+                ]),
+                vec![0, 1, 1, 3, 4, 5, 5, 6, 7, 8, 9]
+            )
+        );
+    }
+}
+// LCOV_EXCL_STOP
@@ -29,6 +29,182 @@ pub fn scan_indents<T: AsRef<str>>(lines: &[T]) -> Vec<usize> {
     adjusted_indents
 }
 
+/// Match a name against a glob pattern with `*` (any sequence) and `?` (any single character)
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last star in the pattern and where its match in the name began
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last star swallow one more character
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Check if a line is a `% indentex: on` or `% indentex: off` pragma and return the new state
+pub fn transpilation_pragma(line: &str) -> Option<bool> {
+    let state = line
+        .trim()
+        .strip_prefix('%')?
+        .trim_start()
+        .strip_prefix("indentex:")?
+        .trim_start();
+    match state {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Get the line which closes a raw `\\begin{...}` of a verbatim environment opened on this line
+pub fn raw_verbatim_end_marker<T: AsRef<str>>(
+    line: &str,
+    verbatim_environments: &[T],
+) -> Option<String> {
+    let rest = line.trim_start().strip_prefix(r"\begin{")?;
+    let (name, _) = rest.split_once('}')?;
+    if !verbatim_environments.iter().any(|e| e.as_ref() == name) {
+        return None;
+    }
+
+    let marker = format!(r"\end{{{}}}", name);
+    // Verbatim environments may be closed on the same line
+    if rest.contains(&marker) {
+        None
+    } else {
+        Some(marker)
+    }
+}
+
+/// Check if a line ends with a single (i.e. not an escaped) backslash outside of a comment
+pub fn ends_with_continuation<T: AsRef<str>>(line: T) -> bool {
+    let mut escaped = false;
+    for c in line.as_ref().chars() {
+        match c {
+            '%' if !escaped => return false,
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    escaped
+}
+
+/// Join a hashline ending with a backslash with the next lines
+///
+/// Returns the logical line and the number of next lines which were joined into it, lines
+/// which are no hashlines are never continued.
+pub fn join_continuation_lines<I, S>(mut line: String, mut next_lines: I) -> (String, usize)
+where
+    I: Iterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut joined = 0;
+    if !line.trim_start_matches(' ').starts_with("# ") {
+        return (line, joined);
+    }
+
+    while ends_with_continuation(&line) {
+        let next = match next_lines.next() {
+            Some(next) => next,
+            None => break,
+        };
+        line.pop();
+        line = trim_end_inplace(line);
+        line.push(' ');
+        line.push_str(next.as_ref().trim());
+        line = trim_end_inplace(line);
+        joined += 1;
+    }
+
+    (line, joined)
+}
+
+/// Commands which go between rows and never end with a row terminator
+const RULE_COMMANDS: [&str; 9] = [
+    r"\hline",
+    r"\cline",
+    r"\toprule",
+    r"\midrule",
+    r"\bottomrule",
+    r"\cmidrule",
+    r"\addlinespace",
+    r"\intertext",
+    r"\shortintertext",
+];
+
+/// Body line of a row environment like `tabular`
+#[derive(Debug, PartialEq)]
+pub enum Row {
+    /// Empty or comment-only line
+    Skip,
+    /// Rule between rows, e.g. `\hline`
+    Rule,
+    /// Row already ending with `\\`
+    Terminated,
+    Unterminated,
+}
+
+/// Split a line before its comment, i.e. the first unescaped `%`
+#[inline]
+pub fn split_comment(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '%' if !escaped => return line.split_at(i),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    (line, "")
+}
+
+pub fn classify_row(line: &str) -> Row {
+    let code = split_comment(line).0.trim();
+    // Row terminators can be starred or have an optional argument, e.g. `\\*[2pt]`
+    let without_opts = match code.strip_suffix(']').and_then(|c| c.rfind('[')) {
+        Some(i) => &code[..i],
+        None => code,
+    };
+    let terminator = without_opts.trim_end_matches('*').trim_end();
+
+    if code.is_empty() {
+        Row::Skip
+    } else if RULE_COMMANDS.iter().any(|r| code.starts_with(r)) {
+        Row::Rule
+    } else if terminator.ends_with(r"\\") || terminator.ends_with(r"\tabularnewline") {
+        Row::Terminated
+    } else {
+        Row::Unterminated
+    }
+}
+
+/// Append `\\` to the code part of a line, keeping its comment
+pub fn terminate_row(line: &str) -> String {
+    let (code, comment) = split_comment(line);
+    let code = code.trim_end();
+    if comment.is_empty() {
+        format!(r"{} \\", code)
+    } else {
+        format!(r"{} \\ {}", code, comment)
+    }
+}
+
 #[inline]
 pub fn trim_end_inplace(mut s: String) -> String {
     let len_to_truncate = s.trim_end().len();
@@ -74,6 +250,127 @@ mod tests {
         assert_eq!(scan_indents(&d).capacity(), 9);
     }
 
+    #[test]
+    fn classify_row() {
+        use super::{classify_row, Row};
+
+        for (line, expected) in [
+            ("", Row::Skip),
+            ("  % foo & bar", Row::Skip),
+            (r"  \hline", Row::Rule),
+            (r"\cmidrule(lr){1-2} % foo", Row::Rule),
+            (r"foo & bar \\", Row::Terminated),
+            (r"foo & bar \\[2pt]", Row::Terminated),
+            (r"foo & bar \\* % baz", Row::Terminated),
+            (r"foo & bar \tabularnewline", Row::Terminated),
+            ("foo & bar", Row::Unterminated),
+            (r"foo & bar \% baz", Row::Unterminated),
+            ("foo & bar[1]", Row::Unterminated),
+            (r"\\foo", Row::Unterminated),
+        ] {
+            assert_eq!(classify_row(line), expected);
+        }
+    }
+
+    #[test]
+    fn terminate_row() {
+        use super::terminate_row;
+
+        assert_eq!(terminate_row("  foo & bar"), r"  foo & bar \\");
+        assert_eq!(terminate_row("foo & bar   % baz"), r"foo & bar \\ % baz");
+        assert_eq!(terminate_row(r"foo & 5\%"), r"foo & 5\% \\");
+    }
+
+    #[test]
+    fn glob_match() {
+        use super::glob_match;
+
+        assert!(glob_match("itemize", "itemize"));
+        assert!(glob_match("itemize*", "itemize"));
+        assert!(glob_match("itemize*", "itemize*"));
+        assert!(glob_match("*enum*", "inparaenum"));
+        assert!(glob_match("task?", "tasks"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("itemize", "itemize*"));
+        assert!(!glob_match("task?", "task"));
+        assert!(!glob_match("a*b", "aXbY"));
+        assert!(!glob_match("", "a"));
+    }
+
+    #[test]
+    fn transpilation_pragma() {
+        use super::transpilation_pragma;
+
+        assert_eq!(transpilation_pragma("% indentex: on"), Some(true));
+        assert_eq!(transpilation_pragma("  %indentex:off"), Some(false));
+        assert_eq!(transpilation_pragma("%   indentex:   off"), Some(false));
+        assert_eq!(transpilation_pragma("% indentex: of"), None);
+        assert_eq!(transpilation_pragma("% indentex: on please"), None);
+        assert_eq!(transpilation_pragma("% indentex on"), None);
+        assert_eq!(transpilation_pragma("foo % indentex: off"), None);
+        assert_eq!(transpilation_pragma(""), None);
+    }
+
+    #[test]
+    fn raw_verbatim_end_marker() {
+        use super::raw_verbatim_end_marker;
+
+        let envs = ["verbatim", "minted"];
+        assert_eq!(
+            raw_verbatim_end_marker(r"  \begin{verbatim}", &envs),
+            Some(r"\end{verbatim}".to_string())
+        );
+        assert_eq!(
+            raw_verbatim_end_marker(r"\begin{minted}{rust}", &envs),
+            Some(r"\end{minted}".to_string())
+        );
+        assert_eq!(
+            raw_verbatim_end_marker(r"\begin{verbatim}x\end{verbatim}", &envs),
+            None
+        );
+        assert_eq!(raw_verbatim_end_marker(r"\begin{lstlisting}", &envs), None);
+        assert_eq!(raw_verbatim_end_marker(r"x \begin{verbatim}", &envs), None);
+        assert_eq!(raw_verbatim_end_marker(r"\begin{verbatim", &envs), None);
+    }
+
+    #[test]
+    fn ends_with_continuation() {
+        use super::ends_with_continuation;
+
+        assert!(ends_with_continuation(r"# foo: bar \"));
+        assert!(ends_with_continuation(r"# foo[bar,\"));
+        assert!(ends_with_continuation(r"\\\"));
+        assert!(ends_with_continuation(r"\% bar \"));
+        assert!(!ends_with_continuation(r"# foo: bar \\"));
+        assert!(!ends_with_continuation(r"# foo: bar % baz \"));
+        assert!(!ends_with_continuation(r"# foo: bar\ "));
+        assert!(!ends_with_continuation(""));
+    }
+
+    #[test]
+    fn join_continuation_lines() {
+        use super::join_continuation_lines;
+
+        let next = ["    bar \\", "  baz", "qux"];
+        assert_eq!(
+            join_continuation_lines(r"  # foo: a \".to_string(), next.iter()),
+            ("  # foo: a bar baz".to_string(), 2)
+        );
+        assert_eq!(
+            join_continuation_lines(r"# foo: a \".to_string(), std::iter::empty::<&str>()),
+            (r"# foo: a \".to_string(), 0)
+        );
+        assert_eq!(
+            join_continuation_lines(r"foo \".to_string(), next.iter()),
+            (r"foo \".to_string(), 0)
+        );
+        assert_eq!(
+            join_continuation_lines(r"# foo: a \\".to_string(), next.iter()),
+            (r"# foo: a \\".to_string(), 0)
+        );
+    }
+
     #[test]
     fn trim_end_inplace() {
         use super::trim_end_inplace;
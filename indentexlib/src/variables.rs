@@ -0,0 +1,163 @@
+use crate::diagnostics::{Diagnostic, DiagnosticCode};
+use crate::regions::Region;
+use std::collections::BTreeMap;
+
+/// Get the length and the name of a `{{name}}` placeholder at the start of the input
+///
+/// Names start with a letter or an underscore and may contain digits, `_`, `-` and `.`,
+/// spaces around the name are allowed.
+fn placeholder(input: &str) -> Option<(usize, &str)> {
+    let inner = input.strip_prefix("{{")?;
+    let end = inner.find("}}")?;
+    let name = inner[..end].trim_matches(' ');
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c));
+
+    if valid {
+        Some((end + 4, name))
+    } else {
+        None
+    }
+}
+
+/// Check if a line may contain a placeholder
+pub fn has_placeholder(line: &str) -> bool {
+    line.contains("{{")
+}
+
+/// Replace all `{{name}}` placeholders of a line with their values
+///
+/// A backslash before a placeholder escapes it, i.e. `\{{name}}` becomes `{{name}}`. Undefined
+/// names are left as they are and reported.
+fn substitute_line(
+    line: &str,
+    line_num: usize,
+    defines: &BTreeMap<String, String>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    let mut substituted = String::with_capacity(line.len());
+    let mut backslashes = 0;
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let (length, name) = match placeholder(rest) {
+            Some(p) => p,
+            None => {
+                backslashes = if c == '\\' { backslashes + 1 } else { 0 };
+                substituted.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+        };
+
+        if backslashes % 2 == 1 {
+            substituted.pop();
+            substituted.push_str(&rest[..length]);
+        } else if let Some(value) = defines.get(name) {
+            substituted.push_str(value);
+        } else {
+            let begin = line[..line.len() - rest.len()].chars().count();
+            diagnostics.push(Diagnostic::new(
+                DiagnosticCode::UndefinedVariable,
+                line_num,
+                begin..begin + rest[..length].chars().count(),
+                &format!("variable '{}' is not defined", name),
+            ));
+            substituted.push_str(&rest[..length]);
+        }
+        backslashes = 0;
+        rest = &rest[length..];
+    }
+
+    substituted
+}
+
+/// Replace all `{{name}}` placeholders with their values
+///
+/// Verbatim bodies and regions where transpilation is switched off are left as they are.
+pub(crate) fn substitute(
+    lines: Vec<String>,
+    regions: &[Region],
+    defines: &BTreeMap<String, String>,
+) -> (Vec<String>, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    let lines = lines
+        .into_iter()
+        .zip(regions)
+        .enumerate()
+        .map(|(i, (l, r))| {
+            if *r != Region::Passthrough && has_placeholder(&l) {
+                substitute_line(&l, i, defines, &mut diagnostics)
+            } else {
+                l
+            }
+        })
+        .collect();
+
+    (lines, diagnostics)
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn placeholder() {
+        use super::placeholder;
+
+        assert_eq!(placeholder("{{version}}"), Some((11, "version")));
+        assert_eq!(
+            placeholder("{{ grant-no.2 }} foo"),
+            Some((16, "grant-no.2"))
+        );
+        assert_eq!(placeholder("{{_x}}}"), Some((6, "_x")));
+        assert_eq!(placeholder("{{}}"), None);
+        assert_eq!(placeholder("{{1x}}"), None);
+        assert_eq!(placeholder("{{a b}}"), None);
+        assert_eq!(placeholder("{{a}\\}"), None);
+        assert_eq!(placeholder("{a}}"), None);
+    }
+
+    #[test]
+    fn substitute() {
+        use super::substitute;
+        use crate::diagnostics::DiagnosticCode;
+        use crate::regions::scan;
+
+        let defines = [("version", "1.2"), ("venue", "ÄCM")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let input = [
+            "Version {{version}} at {{ venue }}",
+            r"\{{version}} \\{{version}} \\\{{venue}}",
+            r"\frac{{a}{b}} {{{venue}}}",
+            "ä {{foo}} {{venue}}",
+            "# verbatim:",
+            r"  \{{version}} {{foo}}",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>();
+        let regions = scan(&input, &Default::default());
+
+        let (lines, diagnostics) = substitute(input, &regions, &defines);
+        assert_eq!(
+            lines,
+            [
+                "Version 1.2 at ÄCM",
+                r"{{version}} \\1.2 \\{{venue}}",
+                r"\frac{{a}{b}} {ÄCM}",
+                "ä {{foo}} ÄCM",
+                "# verbatim:",
+                r"  \{{version}} {{foo}}",
+            ]
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, DiagnosticCode::UndefinedVariable);
+        assert_eq!(diagnostics[0].line, 3);
+        assert_eq!(diagnostics[0].columns, 2..9);
+    }
+}
+// LCOV_EXCL_STOP
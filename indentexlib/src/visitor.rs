@@ -0,0 +1,165 @@
+use crate::document::{Command, Environment, InlineEnvironment, Item};
+
+/// What to emit for a visited node
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rewrite<T> {
+    /// Emit the node unchanged
+    Keep,
+    /// Emit this node instead, formatted like any other node of its kind
+    Modify(T),
+    /// Emit these lines verbatim instead
+    Replace(Vec<String>),
+    /// Emit nothing
+    Drop,
+}
+
+/// Hooks which are called for every node right before it is emitted as LaTeX
+///
+/// The closing hook of an environment receives the environment returned by the opening hook
+/// if it was modified. Dropping or replacing an opening line does not affect the body.
+pub trait Visitor {
+    fn environment_begin(&mut self, _environment: &Environment) -> Rewrite<Environment> {
+        Rewrite::Keep
+    }
+
+    fn environment_end(&mut self, _environment: &Environment) -> Rewrite<Environment> {
+        Rewrite::Keep
+    }
+
+    fn inline_environment(
+        &mut self,
+        _environment: &InlineEnvironment,
+    ) -> Rewrite<InlineEnvironment> {
+        Rewrite::Keep
+    }
+
+    fn command(&mut self, _command: &Command) -> Rewrite<Command> {
+        Rewrite::Keep
+    }
+
+    fn item(&mut self, _item: &Item) -> Rewrite<Item> {
+        Rewrite::Keep
+    }
+
+    fn text(&mut self, _text: &str) -> Rewrite<String> {
+        Rewrite::Keep
+    }
+}
+
+/// Visitor which keeps every node
+pub struct Passthrough;
+
+impl Visitor for Passthrough {}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    use super::{Rewrite, Visitor};
+    use crate::document::{Command, Environment, Item};
+
+    struct HouseStyle;
+
+    impl Visitor for HouseStyle {
+        fn environment_begin(&mut self, environment: &Environment) -> Rewrite<Environment> {
+            match environment.name() {
+                "fig" => Rewrite::Modify(Environment::new(
+                    environment.indent_depth(),
+                    "figure".to_string(),
+                    "[htbp]".to_string(),
+                    environment.comment().to_string(),
+                    false,
+                )),
+                "hidden" => Rewrite::Drop,
+                _ => Rewrite::Keep,
+            }
+        }
+
+        fn environment_end(&mut self, environment: &Environment) -> Rewrite<Environment> {
+            match environment.name() {
+                "figure" => {
+                    Rewrite::Replace(vec!["  \\vspace{1ex}".to_string(), environment.latex_end()])
+                }
+                "hidden" => Rewrite::Drop,
+                _ => Rewrite::Keep,
+            }
+        }
+
+        fn command(&mut self, command: &Command) -> Rewrite<Command> {
+            if command.name() == "section" {
+                Rewrite::Replace(vec![
+                    command.latex(),
+                    format!("\\label{{sec:{}}}", command.args()[0].to_lowercase()),
+                ])
+            } else {
+                Rewrite::Keep
+            }
+        }
+
+        fn item(&mut self, item: &Item) -> Rewrite<Item> {
+            Rewrite::Modify(Item::new(
+                item.indent_depth(),
+                item.content().to_uppercase(),
+            ))
+        }
+
+        fn text(&mut self, text: &str) -> Rewrite<String> {
+            if text.trim_start().starts_with("%") {
+                Rewrite::Drop
+            } else {
+                Rewrite::Keep
+            }
+        }
+    }
+
+    #[test]
+    fn rewrite_nodes() {
+        use crate::{transpile_with_visitor, TranspileOptions};
+
+        let input = (vec![
+            "# section: Intro",
+            "% a comment",
+            "# fig:",
+            "  # includegraphics: foo.pdf",
+            "# hidden:",
+            "  # itemize:",
+            "    * foo",
+            "    bar",
+        ])
+        .into_iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let expected = "\\section{Intro}\n\
+                        \\label{sec:intro}\n\
+                        \\begin{figure}[htbp]\n  \
+                        \\includegraphics{foo.pdf}\n  \
+                        \\vspace{1ex}\n\
+                        \\end{figure}\n  \
+                        \\begin{itemize}\n    \
+                        \\item FOO\n    \
+                        bar\n  \
+                        \\end{itemize}\n";
+        let to = TranspileOptions {
+            prepend_do_not_edit_notice: false,
+            ..Default::default()
+        };
+        let transpiled = transpile_with_visitor(input, &to, &mut HouseStyle);
+        assert_eq!(transpiled.text, expected);
+        assert_eq!(
+            transpiled.source_map.iter().collect::<Vec<_>>(),
+            vec![
+                Some(0),
+                Some(0),
+                Some(2),
+                Some(3),
+                Some(2),
+                Some(2),
+                Some(5),
+                Some(6),
+                Some(7),
+                Some(5),
+            ]
+        );
+    }
+}
+// LCOV_EXCL_STOP
@@ -19,6 +19,7 @@ fn transpile_from_file(test_filename: &str) -> String {
 
     let default_options = indentexlib::TranspileOptions {
         prepend_do_not_edit_notice: true,
+        ..Default::default()
     };
 
     indentexlib::transpile(lines, &default_options)
@@ -33,6 +34,7 @@ fn vanilla_latex_is_not_modified() {
     let expected_lines = lines.clone();
     let no_prepend = indentexlib::TranspileOptions {
         prepend_do_not_edit_notice: false,
+        ..Default::default()
     };
     let transpiled = indentexlib::transpile(lines, &no_prepend);
     let actual_lines = read_and_trim_lines(BufReader::new(transpiled.as_bytes())).unwrap();
@@ -0,0 +1,125 @@
+use crate::error::IndentexError;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the project configuration which is used if none is given explicitly
+pub const DEFAULT_CONFIG_FILE: &str = "indentex.toml";
+
+/// Project configuration, e.g.
+///
+/// ```toml
+/// template_files = ["templates.inden.tex"]
+///
+/// [defines]
+/// draft = true
+/// version = "1.2"
+///
+/// [templates]
+/// fig = """
+/// # figure[htbp]:
+///   \includegraphics#opts{#1}
+///   \caption{#2}
+/// """
+/// ```
+#[derive(Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Template name mapped to its body
+    pub templates: BTreeMap<String, String>,
+    /// Indentex files with `# template:` definitions, relative to the configuration file
+    pub template_files: Vec<PathBuf>,
+    /// Names for conditional blocks, overridden by `-D`
+    pub defines: BTreeMap<String, toml::Value>,
+}
+
+impl Config {
+    pub fn from_file<T: AsRef<Path>>(path: T) -> Result<Self, IndentexError> {
+        let config = std::fs::read_to_string(path.as_ref())?;
+        let mut config: Config = toml::from_str(&config)?;
+
+        let base_dir = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        for f in config.template_files.iter_mut() {
+            *f = base_dir.join(&f);
+        }
+
+        Ok(config)
+    }
+
+    pub fn is_template_file<T: AsRef<Path>>(&self, path: T) -> bool {
+        let canonical = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+        let path = canonical(path.as_ref());
+        self.template_files.iter().any(|f| canonical(f) == path)
+    }
+
+    /// Get the defines as strings, e.g. `true` or `1.2`
+    pub fn defines(&self) -> BTreeMap<String, String> {
+        self.defines
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    toml::Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                };
+                (name.clone(), value)
+            })
+            .collect()
+    }
+
+    /// Collect all templates, inline ones first
+    pub fn templates(&self) -> Result<Vec<indentexlib::templates::Template>, IndentexError> {
+        use crate::file_utils::read_and_trim_lines;
+        use indentexlib::templates::{parse_templates, Template};
+
+        let mut templates: Vec<Template> = self
+            .templates
+            .iter()
+            .map(|(name, body)| Template::new(name.clone(), body))
+            .collect();
+        for f in self.template_files.iter() {
+            templates.extend(parse_templates(&read_and_trim_lines(f)?));
+        }
+
+        Ok(templates)
+    }
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn parse() {
+        let config: Config = toml::from_str(
+            "template_files = [\"t.inden.tex\"]\n\
+             [templates]\n\
+             hl = \"# textbf: #1\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.template_files,
+            [std::path::PathBuf::from("t.inden.tex")]
+        );
+        assert_eq!(config.templates["hl"], "# textbf: #1");
+
+        let templates = Config {
+            template_files: Vec::new(),
+            ..config
+        }
+        .templates()
+        .unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].name, "hl");
+
+        let config: Config =
+            toml::from_str("[defines]\ndraft = true\nreview = 0\nversion = \"1.2\"\n").unwrap();
+        let defines = config.defines();
+        assert_eq!(defines["draft"], "true");
+        assert_eq!(defines["review"], "0");
+        assert_eq!(defines["version"], "1.2");
+
+        assert_eq!(toml::from_str::<Config>("").unwrap(), Config::default());
+        assert!(toml::from_str::<Config>("foo = 1").is_err());
+    }
+}
+// LCOV_EXCL_STOP
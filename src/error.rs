@@ -2,7 +2,13 @@
 pub enum IndentexError {
     Io(std::io::Error),
     InvalidExtension,
+    InvalidLatexExtension,
+    OutputExists(std::path::PathBuf),
     WalkError(ignore::Error),
+    Json(serde_json::Error),
+    Config(toml::de::Error),
+    FilterFailed(String, std::process::ExitStatus),
+    SuspiciousInput(usize),
 }
 
 impl From<ignore::Error> for IndentexError {
@@ -11,6 +17,18 @@ impl From<ignore::Error> for IndentexError {
     }
 }
 
+impl From<serde_json::Error> for IndentexError {
+    fn from(e: serde_json::Error) -> IndentexError {
+        IndentexError::Json(e)
+    }
+}
+
+impl From<toml::de::Error> for IndentexError {
+    fn from(e: toml::de::Error) -> IndentexError {
+        IndentexError::Config(e)
+    }
+}
+
 impl From<std::io::Error> for IndentexError {
     fn from(e: std::io::Error) -> IndentexError {
         IndentexError::Io(e)
@@ -22,7 +40,15 @@ impl std::fmt::Display for IndentexError {
         match *self {
             IndentexError::Io(ref e) => e.fmt(f),
             IndentexError::InvalidExtension => write!(f, "not a valid indentex file"),
+            IndentexError::InvalidLatexExtension => write!(f, "not a LaTeX file"),
+            IndentexError::OutputExists(ref p) => write!(f, "'{}' already exists", p.display()),
             IndentexError::WalkError(ref e) => e.fmt(f),
+            IndentexError::Json(ref e) => e.fmt(f),
+            IndentexError::Config(ref e) => write!(f, "invalid configuration: {}", e),
+            IndentexError::FilterFailed(ref filter, ref status) => {
+                write!(f, "filter '{}' failed ({})", filter, status)
+            }
+            IndentexError::SuspiciousInput(n) => write!(f, "{} error(s) in the input", n),
         }
     }
 }
@@ -34,7 +60,7 @@ mod tests {
 
     #[test]
     fn from_io_error() {
-        let io_error = std::io::Error::new(std::io::ErrorKind::Other, "foo");
+        let io_error = std::io::Error::other("foo");
         let io_error_debug = format!("{:?}", io_error);
         let err = IndentexError::from(io_error);
         assert_eq!(format!("{}", err), "foo");
@@ -43,7 +69,7 @@ mod tests {
 
     #[test]
     fn from_ignore_error() {
-        let io_error = std::io::Error::new(std::io::ErrorKind::Other, "bar");
+        let io_error = std::io::Error::other("bar");
         let ignore_error = ignore::Error::Io(io_error);
         let ignore_error_debug = format!("{:?}", ignore_error);
         let err = IndentexError::from(ignore_error);
@@ -54,11 +80,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn from_json_error() {
+        let json_error = serde_json::from_str::<u8>("foo").unwrap_err();
+        let json_error_display = format!("{}", json_error);
+        let err = IndentexError::from(json_error);
+        assert_eq!(format!("{}", err), json_error_display);
+        assert!(format!("{:?}", err).starts_with("Json("));
+    }
+
+    #[test]
+    fn from_toml_error() {
+        let toml_error = toml::from_str::<u8>("foo").unwrap_err();
+        let err = IndentexError::from(toml_error);
+        assert!(format!("{}", err).starts_with("invalid configuration: "));
+        assert!(format!("{:?}", err).starts_with("Config("));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn filter_failed() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let err = IndentexError::FilterFailed("./foo".to_string(), ExitStatusExt::from_raw(256));
+        assert_eq!(format!("{}", err), "filter './foo' failed (exit status: 1)");
+    }
+
     #[test]
     fn invalid_extension() {
         let err = IndentexError::InvalidExtension;
         assert_eq!(format!("{}", err), "not a valid indentex file");
         assert_eq!(format!("{:?}", err), "InvalidExtension");
     }
+
+    #[test]
+    fn suspicious_input() {
+        let err = IndentexError::SuspiciousInput(2);
+        assert_eq!(format!("{}", err), "2 error(s) in the input");
+    }
+
+    #[test]
+    fn output_exists() {
+        let err = IndentexError::OutputExists(std::path::PathBuf::from("foo.inden.tex"));
+        assert_eq!(format!("{}", err), "'foo.inden.tex' already exists");
+    }
 }
 // LCOV_EXCL_STOP
@@ -55,6 +55,23 @@ pub fn rename_indentex_file<T: AsRef<Path>>(old_path: T) -> Result<PathBuf, Inde
     Ok(new_pathbuf)
 }
 
+/// Get the indentex path for a plain LaTeX file, i.e. `*.tex` becomes `*.inden.tex`
+pub fn latex_to_indentex_path<T: AsRef<Path>>(latex_path: T) -> Result<PathBuf, IndentexError> {
+    let path = latex_path.as_ref();
+    if path.extension().is_none_or(|e| e != "tex") || is_indentex_file(path) {
+        return Err(IndentexError::InvalidLatexExtension);
+    }
+
+    Ok(path.with_extension("inden.tex"))
+}
+
+/// Get the path of the line map sidecar for a transpiled file, i.e. `*_indentex.tex.map`
+pub fn source_map_path<T: AsRef<Path>>(transpiled_path: T) -> PathBuf {
+    let mut name = transpiled_path.as_ref().as_os_str().to_os_string();
+    name.push(".map");
+    PathBuf::from(name)
+}
+
 /// Read a file line by line, trim the ends of lines and _copy_ them into a vec of strings
 pub fn read_and_trim_lines<T: AsRef<Path>>(path: T) -> Result<Vec<String>, IndentexError> {
     if !is_indentex_file(path.as_ref()) {
@@ -82,6 +99,49 @@ where
     Ok(())
 }
 
+#[inline]
+fn is_gzipped<T: AsRef<Path>>(path: T) -> bool {
+    path.as_ref().extension().is_some_and(|e| e == "gz")
+}
+
+/// Read a whole text file, decompress it first if it has a `.gz` extension
+pub fn read_maybe_gzipped<T: AsRef<Path>>(path: T) -> Result<String, IndentexError> {
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    let file = std::fs::File::open(path.as_ref())?;
+    let mut data = String::new();
+    if is_gzipped(path.as_ref()) {
+        GzDecoder::new(file).read_to_string(&mut data)?;
+    } else {
+        std::io::BufReader::new(file).read_to_string(&mut data)?;
+    }
+
+    Ok(data)
+}
+
+/// Write a whole text file, compress it if it has a `.gz` extension
+pub fn write_maybe_gzipped<T, U>(path: T, data: U) -> Result<(), IndentexError>
+where
+    T: AsRef<Path>,
+    U: AsRef<str>,
+{
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    if !is_gzipped(path.as_ref()) {
+        return write_to_file(path, data);
+    }
+
+    let file = std::fs::File::create(path.as_ref())?;
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    encoder.write_all(data.as_ref().as_bytes())?;
+    encoder.finish()?;
+
+    Ok(())
+}
+
 // LCOV_EXCL_START
 #[cfg(test)]
 mod tests {
@@ -121,5 +181,45 @@ mod tests {
         );
         assert!(rename_indentex_file(Path::new("foo.bar.tex")).is_err())
     }
+
+    #[test]
+    fn is_gzipped() {
+        use super::is_gzipped;
+
+        assert!(is_gzipped(Path::new("foo.synctex.gz")));
+        assert!(!is_gzipped(Path::new("foo.synctex")));
+        assert!(!is_gzipped(Path::new("gz")));
+    }
+
+    #[test]
+    fn latex_to_indentex_path() {
+        use super::latex_to_indentex_path;
+
+        assert_eq!(
+            latex_to_indentex_path(Path::new("./foo.tex")).unwrap(),
+            PathBuf::from("./foo.inden.tex")
+        );
+        assert_eq!(
+            latex_to_indentex_path(Path::new("foo.bar.tex")).unwrap(),
+            PathBuf::from("foo.bar.inden.tex")
+        );
+        assert!(latex_to_indentex_path(Path::new("foo.inden.tex")).is_err());
+        assert!(latex_to_indentex_path(Path::new("foo.txt")).is_err());
+        assert!(latex_to_indentex_path(Path::new("tex")).is_err());
+    }
+
+    #[test]
+    fn source_map_path() {
+        use super::source_map_path;
+
+        assert_eq!(
+            source_map_path(Path::new("./foo_indentex.tex")),
+            PathBuf::from("./foo_indentex.tex.map")
+        );
+        assert_eq!(
+            source_map_path(Path::new("foo.bar_indentex.tex")),
+            PathBuf::from("foo.bar_indentex.tex.map")
+        );
+    }
 }
 // LCOV_EXCL_STOP
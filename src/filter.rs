@@ -0,0 +1,92 @@
+use crate::error::IndentexError;
+use indentexlib::document::Document;
+
+/// Pipe a document as JSON through an external program and read the modified document back
+pub fn run_filter(filter: &str, document: &Document) -> Result<Document, IndentexError> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let input = serde_json::to_vec(document)?;
+
+    let mut child = Command::new(filter)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // Write in a separate thread, otherwise a filter which starts writing before it has read
+    // the whole input would deadlock once both pipe buffers are full
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    let write_result = writer.join().unwrap();
+
+    if !output.status.success() {
+        return Err(IndentexError::FilterFailed(
+            filter.to_string(),
+            output.status,
+        ));
+    }
+    // A filter may legitimately ignore its input, what counts is the output
+    if let Err(e) = write_result {
+        if e.kind() != std::io::ErrorKind::BrokenPipe {
+            return Err(e.into());
+        }
+    }
+
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// Run all filters one after another
+pub fn run_filters(filters: &[String], mut document: Document) -> Result<Document, IndentexError> {
+    for filter in filters {
+        document = run_filter(filter, &document)?;
+    }
+    Ok(document)
+}
+
+// LCOV_EXCL_START
+#[cfg(all(test, unix))]
+mod tests {
+    use super::run_filters;
+
+    fn document() -> indentexlib::document::Document {
+        let lines = (vec!["# foo:", "  # bar: baz", "qux"])
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+        indentexlib::parse(lines, &Default::default())
+    }
+
+    #[test]
+    fn identity_filters() {
+        let filters = vec!["cat".to_string(), "cat".to_string()];
+        assert_eq!(run_filters(&filters, document()).unwrap(), document());
+        assert_eq!(run_filters(&[], document()).unwrap(), document());
+    }
+
+    #[test]
+    fn failing_filter() {
+        use crate::error::IndentexError;
+
+        let filters = vec!["cat".to_string(), "false".to_string()];
+        match run_filters(&filters, document()) {
+            Err(IndentexError::FilterFailed(f, status)) => {
+                assert_eq!(f, "false");
+                assert_eq!(status.code(), Some(1));
+            }
+            r => panic!("expected a failed filter, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn invalid_output() {
+        use crate::error::IndentexError;
+
+        let filters = vec!["true".to_string()];
+        assert!(matches!(
+            run_filters(&filters, document()),
+            Err(IndentexError::Json(_))
+        ));
+    }
+}
+// LCOV_EXCL_STOP
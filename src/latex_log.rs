@@ -0,0 +1,247 @@
+use crate::remap::Remapper;
+
+/// Line references which refer to the file that is currently open
+const LINE_REFERENCE_PREFIXES: [&str; 3] = ["on input line ", "at lines ", "at line "];
+
+/// Rewrite file and line references to transpiled files in a LaTeX log
+///
+/// The currently open file is tracked by the parentheses TeX writes when opening and closing
+/// files. Both `l.123` error contexts and `file:123:` messages (`-file-line-error`) are rewritten.
+pub fn remap_log(log: &str, remapper: &mut Remapper) -> String {
+    let mut file_stack: Vec<Option<String>> = Vec::new();
+    let mut remapped = String::with_capacity(log.len());
+
+    for line in log.lines() {
+        let current_file = file_stack.iter().rev().find_map(|f| f.clone());
+
+        let mut new_line = match current_file {
+            Some(ref f) => remap_line_references(line, f, remapper),
+            None => line.to_string(),
+        };
+        new_line = remap_file_line_error(&new_line, remapper);
+        // Error contexts contain source code, so their parentheses do not open or close files
+        if !line.starts_with("l.") {
+            new_line = remap_file_names(&new_line, &mut file_stack);
+        }
+
+        remapped.push_str(&new_line);
+        remapped.push('\n');
+    }
+
+    remapped
+}
+
+/// Replace every number directly following `prefix`
+fn remap_numbers_after<F>(line: &str, prefix: &str, mut f: F) -> String
+where
+    F: FnMut(usize) -> Option<usize>,
+{
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(pos) = rest.find(prefix) {
+        let after_prefix = pos + prefix.len();
+        result.push_str(&rest[..after_prefix]);
+        rest = &rest[after_prefix..];
+
+        let digits_len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let number = rest[..digits_len].parse::<usize>().ok();
+        match number.and_then(&mut f) {
+            Some(n) => result.push_str(&n.to_string()),
+            None => result.push_str(&rest[..digits_len]),
+        }
+        rest = &rest[digits_len..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+fn remap_line_references(line: &str, file: &str, remapper: &mut Remapper) -> String {
+    let mut new_line = match line.strip_prefix("l.") {
+        Some(rest) => {
+            let digits_len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            match rest[..digits_len].parse::<usize>().ok() {
+                Some(n) => format!(
+                    "l.{}{}",
+                    remapper.source_line(file, n).unwrap_or(n),
+                    &rest[digits_len..]
+                ),
+                None => line.to_string(),
+            }
+        }
+        None => line.to_string(),
+    };
+
+    for prefix in LINE_REFERENCE_PREFIXES.iter() {
+        new_line = remap_numbers_after(&new_line, prefix, |n| remapper.source_line(file, n));
+    }
+    if new_line.contains("at lines ") {
+        new_line = remap_numbers_after(&new_line, "--", |n| remapper.source_line(file, n));
+    }
+
+    new_line
+}
+
+/// Rewrite messages of the form `./foo_indentex.tex:123: Error message`
+///
+/// The file name ends at the first `:123:`, so that Windows paths like `C:\foo_indentex.tex`
+/// are kept intact. Lines without a source line, e.g. the ones of the notice, are not rewritten.
+fn remap_file_line_error(line: &str, remapper: &mut Remapper) -> String {
+    for (pos, _) in line.match_indices(':') {
+        let rest = &line[pos + 1..];
+        let digits_len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits_len == 0 || !rest[digits_len..].starts_with(':') {
+            continue;
+        }
+
+        let file = &line[..pos];
+        let source = Remapper::source_name(file).and_then(|source_name| {
+            let n = rest[..digits_len].parse::<usize>().ok()?;
+            Some((source_name, remapper.source_line(file, n)?))
+        });
+        return match source {
+            Some((source_name, n)) => format!("{}:{}{}", source_name, n, &rest[digits_len..]),
+            None => line.to_string(),
+        };
+    }
+
+    line.to_string()
+}
+
+/// Track opened and closed files and rename the transpiled ones
+fn remap_file_names(line: &str, file_stack: &mut Vec<Option<String>>) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(pos) = rest.find(['(', ')']) {
+        result.push_str(&rest[..=pos]);
+        let paren = rest.as_bytes()[pos];
+        rest = &rest[pos + 1..];
+
+        if paren == b')' {
+            file_stack.pop();
+            continue;
+        }
+
+        let name_len = rest
+            .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .unwrap_or(rest.len());
+        let name = &rest[..name_len];
+        if name.contains('.') {
+            file_stack.push(Some(name.to_string()));
+            result.push_str(&Remapper::source_name(name).unwrap_or_else(|| name.to_string()));
+        } else {
+            file_stack.push(None);
+            result.push_str(name);
+        }
+        rest = &rest[name_len..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    use super::remap_log;
+    use crate::remap::Remapper;
+    use indentexlib::source_map::SourceMap;
+    use indentexlib::TranspileOptions;
+
+    fn with_remapper<F: FnOnce(&mut Remapper)>(f: F) {
+        let options = TranspileOptions {
+            prepend_do_not_edit_notice: false,
+            ..Default::default()
+        };
+        let mut remapper = Remapper::new("/nonexistent", &options);
+        // Output lines 1 to 6 are mapped to source lines -, 10, 20, 30, 30, -
+        remapper.insert(
+            "./foo_indentex.tex",
+            SourceMap::from_sidecar("0\n10\n20\n30\n30\n0\n").unwrap(),
+        );
+        f(&mut remapper);
+    }
+
+    #[test]
+    fn error_context() {
+        with_remapper(|r| {
+            let log = "(./main.tex (./foo_indentex.tex\n\
+                       ! Undefined control sequence.\n\
+                       l.3 \\foo\n\
+                       ) (./bar.tex\n\
+                       l.3 \\foo (bar)\n\
+                       ))\n";
+            let expected = "(./main.tex (./foo.inden.tex\n\
+                            ! Undefined control sequence.\n\
+                            l.20 \\foo\n\
+                            ) (./bar.tex\n\
+                            l.3 \\foo (bar)\n\
+                            ))\n";
+            assert_eq!(remap_log(log, r), expected);
+        });
+    }
+
+    #[test]
+    fn warnings() {
+        with_remapper(|r| {
+            let log = "(./foo_indentex.tex (./baz.sty)\n\
+                       LaTeX Warning: Reference `x' undefined on input line 4.\n\
+                       Overfull \\hbox (1.0pt too wide) in paragraph at lines 2--4\n\
+                       Underfull \\hbox (badness 10000) detected at line 1\n\
+                       Unknown line at lines 7--8\n\
+                       )\n\
+                       LaTeX Warning: Reference `y' undefined on input line 4.\n";
+            let expected = "(./foo.inden.tex (./baz.sty)\n\
+                            LaTeX Warning: Reference `x' undefined on input line 30.\n\
+                            Overfull \\hbox (1.0pt too wide) in paragraph at lines 10--30\n\
+                            Underfull \\hbox (badness 10000) detected at line 1\n\
+                            Unknown line at lines 7--8\n\
+                            )\n\
+                            LaTeX Warning: Reference `y' undefined on input line 4.\n";
+            assert_eq!(remap_log(log, r), expected);
+        });
+    }
+
+    #[test]
+    fn file_line_error() {
+        with_remapper(|r| {
+            let log = "./foo_indentex.tex:3: Undefined control sequence.\n\
+                       ./main.tex:3: Undefined control sequence.\n\
+                       ./foo_indentex.tex:1: Missing $ inserted.\n\
+                       ./foo_indentex.tex:6: Missing $ inserted.\n\
+                       ./foo_indentex.tex:bar: baz\n\
+                       ./foo_indentex.tex:4: Use of \\a:12: doesn't match\n";
+            let expected = "./foo.inden.tex:20: Undefined control sequence.\n\
+                            ./main.tex:3: Undefined control sequence.\n\
+                            ./foo_indentex.tex:1: Missing $ inserted.\n\
+                            ./foo_indentex.tex:6: Missing $ inserted.\n\
+                            ./foo_indentex.tex:bar: baz\n\
+                            ./foo.inden.tex:30: Use of \\a:12: doesn't match\n";
+            assert_eq!(remap_log(log, r), expected);
+        });
+    }
+
+    #[test]
+    fn file_line_error_with_drive_letter() {
+        with_remapper(|r| {
+            r.insert(
+                r"C:\x\foo_indentex.tex",
+                SourceMap::from_sidecar("0\n10\n").unwrap(),
+            );
+            let log = "C:\\x\\foo_indentex.tex:2: Undefined control sequence.\n\
+                       C:\\x\\foo_indentex.tex:1: Missing $ inserted.\n";
+            let expected = "C:\\x\\foo.inden.tex:10: Undefined control sequence.\n\
+                            C:\\x\\foo_indentex.tex:1: Missing $ inserted.\n";
+            assert_eq!(remap_log(log, r), expected);
+        });
+    }
+}
+// LCOV_EXCL_STOP
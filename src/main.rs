@@ -1,31 +1,150 @@
+mod config;
 mod error;
 mod file_utils;
+mod filter;
+mod latex_log;
+mod remap;
+mod synctex;
 
 enum ReturnCode {
     Ok = 0,
     WalkError = 2,
     FileTypeError = 4,
     TranspilationError = 8,
+    RemapError = 16,
+    ConfigError = 32,
+    SuspiciousInput = 64,
 }
 
 pub fn transpile_file<T: AsRef<std::path::Path>>(
     path: T,
     options: &indentexlib::TranspileOptions,
+    write_source_map: bool,
+    filters: &[String],
 ) -> Result<(), crate::error::IndentexError> {
-    use crate::file_utils::{read_and_trim_lines, rename_indentex_file, write_to_file};
+    use crate::file_utils::{
+        read_and_trim_lines, rename_indentex_file, source_map_path, write_to_file,
+    };
+    use crate::filter::run_filters;
+    use indentexlib::diagnostics::Severity;
 
     let lines = read_and_trim_lines(path.as_ref())?;
-    let transpiled_text = indentexlib::transpile(lines, options);
+    let transpiled = if filters.is_empty() {
+        indentexlib::transpile_checked(lines, options)
+    } else {
+        let (document, diagnostics) = indentexlib::parse_checked(lines, options);
+        let document = run_filters(filters, document)?;
+        let mut transpiled = indentexlib::render(&document, options);
+        transpiled.diagnostics = diagnostics;
+        transpiled
+    };
+    let mut errors = 0;
+    for d in transpiled.diagnostics.iter() {
+        match d.severity {
+            Severity::Error => {
+                errors += 1;
+                log::error!("{}:{}", path.as_ref().display(), d)
+            }
+            Severity::Warning => log::warn!("{}:{}", path.as_ref().display(), d),
+        }
+    }
     let path_out = rename_indentex_file(path)?;
-    write_to_file(path_out, &transpiled_text)?;
+    if write_source_map {
+        write_to_file(
+            source_map_path(&path_out),
+            transpiled.source_map.to_sidecar(),
+        )?;
+    }
+    write_to_file(path_out, &transpiled.text)?;
+
+    // The output is still written, but the input is flagged
+    if errors > 0 {
+        return Err(crate::error::IndentexError::SuspiciousInput(errors));
+    }
 
     Ok(())
 }
 
+pub fn remap_log_file<T: AsRef<std::path::Path>>(
+    path: T,
+    options: &indentexlib::TranspileOptions,
+) -> Result<String, crate::error::IndentexError> {
+    use crate::latex_log::remap_log;
+    use crate::remap::Remapper;
+
+    let log = std::fs::read_to_string(path.as_ref())?;
+    let base_dir = path
+        .as_ref()
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new(""));
+    let mut remapper = Remapper::new(base_dir, options);
+
+    Ok(remap_log(&log, &mut remapper))
+}
+
+pub fn remap_synctex_file<T: AsRef<std::path::Path>>(
+    path: T,
+    options: &indentexlib::TranspileOptions,
+) -> Result<(), crate::error::IndentexError> {
+    use crate::file_utils::{read_maybe_gzipped, write_maybe_gzipped};
+    use crate::remap::Remapper;
+    use crate::synctex::remap_synctex;
+
+    let synctex = read_maybe_gzipped(path.as_ref())?;
+    let base_dir = path
+        .as_ref()
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new(""));
+    let mut remapper = Remapper::new(base_dir, options);
+    write_maybe_gzipped(path.as_ref(), remap_synctex(&synctex, &mut remapper))?;
+
+    Ok(())
+}
+
+pub fn dump_file<T: AsRef<std::path::Path>>(
+    path: T,
+    json: bool,
+    options: &indentexlib::TranspileOptions,
+) -> Result<String, crate::error::IndentexError> {
+    use crate::file_utils::read_and_trim_lines;
+
+    let lines = read_and_trim_lines(path.as_ref())?;
+    let document = indentexlib::parse(lines, options);
+
+    if json {
+        Ok(serde_json::to_string_pretty(&document)?)
+    } else {
+        Ok(format!("{:#?}", document))
+    }
+}
+
+pub fn from_latex_file<T: AsRef<std::path::Path>>(
+    path: T,
+) -> Result<std::path::PathBuf, crate::error::IndentexError> {
+    use crate::error::IndentexError;
+    use crate::file_utils::{latex_to_indentex_path, write_to_file};
+
+    let path_out = latex_to_indentex_path(path.as_ref())?;
+    // Never overwrite sources
+    if path_out.exists() {
+        return Err(IndentexError::OutputExists(path_out));
+    }
+
+    let file = std::fs::File::open(path.as_ref())?;
+    let lines = indentexlib::preprocessing::read_and_trim_lines(std::io::BufReader::new(file))?;
+    write_to_file(&path_out, indentexlib::from_latex(&lines))?;
+
+    Ok(path_out)
+}
+
 fn main() {
+    use crate::config::{Config, DEFAULT_CONFIG_FILE};
+    use crate::error::IndentexError;
     use crate::file_utils::walk_indentex_files;
-    use clap::{crate_authors, crate_description, crate_version, App, Arg};
-    use indentexlib::TranspileOptions;
+    use clap::{
+        crate_authors, crate_description, crate_version, App, AppSettings, Arg, SubCommand,
+    };
+    use indentexlib::{ItemMarker, ListEnvironment, TranspileOptions, DEFAULT_ROW_ENVIRONMENTS};
     use rayon::prelude::*;
     use std::path::{Path, PathBuf};
 
@@ -33,6 +152,7 @@ fn main() {
         .version(crate_version!())
         .author(crate_authors!())
         .about(crate_description!())
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("path")
                 .help(
@@ -51,7 +171,208 @@ fn main() {
         .arg(
             Arg::with_name("disable-do-not-edit")
                 .help("Disable prepending the 'DO NOT EDIT' notice")
-                .long("disable-do-not-edit"),
+                .long("disable-do-not-edit")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("verbatim")
+                .help(
+                    "Pass the bodies of this environment through unchanged, in addition to \
+                     verbatim, verbatim*, lstlisting, minted and comment (can be given multiple \
+                     times)",
+                )
+                .long("verbatim")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("list-env")
+                .help(
+                    "Expand itemlines in this environment (a name or a glob like 'enum*') into \
+                     the given command, e.g. 'thebibliography=bibitem' or 'tasks=task', without \
+                     '=' into 'item' (can be given multiple times, first match wins)",
+                )
+                .long("list-env")
+                .value_name("pattern[=command]")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("item-marker")
+                .help(
+                    "Also start itemlines with this marker, 'N.' accepts both '1.' and '1)' \
+                     (can be given multiple times, '*' is always accepted)",
+                )
+                .long("item-marker")
+                .takes_value(true)
+                .possible_values(&["-", "+", "N.", "N)"])
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("item-numbering")
+                .help(
+                    "Keep the numbers of numbered itemlines with '\\setcounter' or as an \
+                     '\\item' label, or leave the numbering to LaTeX",
+                )
+                .long("item-numbering")
+                .takes_value(true)
+                .possible_values(&["ignore", "setcounter", "label"])
+                .default_value("ignore")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("implicit-lists")
+                .help("Wrap itemlines outside of list environments into an 'itemize'")
+                .long("implicit-lists")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("rows")
+                .help(
+                    "Terminate the rows of tabular*, array, align*, gather* and *matrix \
+                     environments with '\\\\' automatically",
+                )
+                .long("rows")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("row-env")
+                .help(
+                    "Terminate the rows of this environment (a name or a glob like 'tabu*') \
+                     automatically (can be given multiple times)",
+                )
+                .long("row-env")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("headings")
+                .help(
+                    "Turn lines like '== Foo {#sec:foo}' into sectioning commands, starting with \
+                     '\\section' for articles or with '\\part' for books",
+                )
+                .long("headings")
+                .takes_value(true)
+                .possible_values(&["off", "article", "book"])
+                .default_value("off")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .help("Path to the project configuration [default: ./indentex.toml if it exists]")
+                .long("config")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("define")
+                .help(
+                    "Define a name for conditional blocks like '# ?if draft:' and '{{name}}' \
+                     variables, without '=' the value is 'true' (can be given multiple times, \
+                     overrides the configuration)",
+                )
+                .short("D")
+                .long("define")
+                .value_name("name[=value]")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("templates")
+                .help(
+                    "Expand the templates defined with '# template: name' in this indentex file \
+                     (can be given multiple times)",
+                )
+                .long("templates")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .help(
+                    "Pipe the document tree as JSON through an executable before emitting LaTeX \
+                     (can be given multiple times, filters run in the given order)",
+                )
+                .long("filter")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("backend")
+                .help("Output format")
+                .long("backend")
+                .takes_value(true)
+                .possible_values(&["latex", "context"])
+                .default_value("latex"),
+        )
+        .arg(
+            Arg::with_name("source-map")
+                .help("Write a line map next to each transpiled file")
+                .long("source-map"),
+        )
+        .subcommand(
+            SubCommand::with_name("remap-log")
+                .about(
+                    "Rewrite references to transpiled files in a LaTeX log to their indentex \
+                     sources and print the result",
+                )
+                .arg(
+                    Arg::with_name("log")
+                        .help("Path to a LaTeX log file")
+                        .index(1)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("remap-synctex")
+                .about(
+                    "Rewrite a SyncTeX file in place so that it refers to the indentex sources \
+                     instead of the transpiled files",
+                )
+                .arg(
+                    Arg::with_name("synctex")
+                        .help("Path to a '.synctex.gz' or '.synctex' file")
+                        .index(1)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dump")
+                .about("Print the document tree of an indentex file")
+                .arg(
+                    Arg::with_name("file")
+                        .help("Path to a single indentex file")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Print the document tree as JSON")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("from-latex")
+                .about("Convert a plain LaTeX file into an indentex file next to it")
+                .arg(
+                    Arg::with_name("file")
+                        .help("Path to a '.tex' file")
+                        .index(1)
+                        .required(true),
+                ),
         )
         .get_matches();
 
@@ -59,22 +380,166 @@ fn main() {
         0 => log::LevelFilter::Warn,
         1 => log::LevelFilter::Info,
         2 => log::LevelFilter::Debug,
-        3 | _ => log::LevelFilter::Trace,
+        _ => log::LevelFilter::Trace,
     };
     env_logger::Builder::new().filter_level(log_level).init();
 
-    let path = Path::new(m.value_of("path").unwrap());
-    let options = TranspileOptions {
+    let mut options = TranspileOptions {
         prepend_do_not_edit_notice: !m.is_present("disable-do-not-edit"),
+        // Validated by clap
+        backend: m.value_of("backend").unwrap().parse().unwrap(),
+        item_numbering: m.value_of("item-numbering").unwrap().parse().unwrap(),
+        implicit_lists: m.is_present("implicit-lists"),
+        headings: m.value_of("headings").unwrap().parse().unwrap(),
+        ..Default::default()
+    };
+    if let Some(envs) = m.values_of("verbatim") {
+        options
+            .verbatim_environments
+            .extend(envs.map(|e| e.to_string()));
+    }
+    if let Some(markers) = m.values_of("item-marker") {
+        // Validated by clap
+        options
+            .item_markers
+            .extend(markers.map(|i| i.parse::<ItemMarker>().unwrap()));
+        options.item_markers.dedup();
+    }
+    if m.is_present("rows") {
+        options
+            .row_environments
+            .extend(DEFAULT_ROW_ENVIRONMENTS.iter().map(|e| e.to_string()));
+    }
+    if let Some(envs) = m.values_of("row-env") {
+        options.row_environments.extend(envs.map(|e| e.to_string()));
+    }
+    if let Some(envs) = m.values_of("list-env") {
+        // User-defined environments take precedence over the defaults
+        let user_envs = envs.map(|e| match e.find('=') {
+            Some(i) => ListEnvironment::new(e[..i].to_string(), e[i + 1..].to_string()),
+            None => ListEnvironment::new(e.to_string(), "item".to_string()),
+        });
+        options.list_environments.splice(0..0, user_envs);
+    }
+
+    let config = match m.value_of("config") {
+        Some(c) => Config::from_file(c).map(Some),
+        None if Path::new(DEFAULT_CONFIG_FILE).is_file() => {
+            Config::from_file(DEFAULT_CONFIG_FILE).map(Some)
+        }
+        None => Ok(None),
     };
+    let mut config = match config {
+        Ok(c) => c.unwrap_or_default(),
+        Err(e) => {
+            log::error!("Loading the configuration ... failed: {}", e);
+            std::process::exit(ReturnCode::ConfigError as i32);
+        }
+    };
+    if let Some(files) = m.values_of("templates") {
+        config.template_files.extend(files.map(PathBuf::from));
+    }
+    match config.templates() {
+        Ok(t) => options.templates = t,
+        Err(e) => {
+            log::error!("Loading the templates ... failed: {}", e);
+            std::process::exit(ReturnCode::ConfigError as i32);
+        }
+    }
+    options.defines = config.defines();
+    if let Some(defines) = m.values_of("define") {
+        options.defines.extend(defines.map(|d| match d.find('=') {
+            Some(i) => (d[..i].to_string(), d[i + 1..].to_string()),
+            None => (d.to_string(), "true".to_string()),
+        }));
+    }
+
+    if let Some(sm) = m.subcommand_matches("remap-log") {
+        let log_path = Path::new(sm.value_of("log").unwrap());
+        let ret_val = match remap_log_file(log_path, &options) {
+            Ok(remapped) => {
+                print!("{}", remapped);
+                ReturnCode::Ok
+            }
+            Err(e) => {
+                log::error!("Remapping log '{}' ... failed: {}", log_path.display(), e);
+                ReturnCode::RemapError
+            }
+        };
+        std::process::exit(ret_val as i32);
+    }
+
+    if let Some(sm) = m.subcommand_matches("dump") {
+        let file_path = Path::new(sm.value_of("file").unwrap());
+        let ret_val = match dump_file(file_path, sm.is_present("json"), &options) {
+            Ok(dumped) => {
+                println!("{}", dumped);
+                ReturnCode::Ok
+            }
+            Err(e) => {
+                log::error!("Dumping file '{}' ... failed: {}", file_path.display(), e);
+                ReturnCode::TranspilationError
+            }
+        };
+        std::process::exit(ret_val as i32);
+    }
+
+    if let Some(sm) = m.subcommand_matches("remap-synctex") {
+        let synctex_path = Path::new(sm.value_of("synctex").unwrap());
+        let ret_val = match remap_synctex_file(synctex_path, &options) {
+            Ok(_) => {
+                log::info!("Remapping SyncTeX '{}' ... ok", synctex_path.display());
+                ReturnCode::Ok
+            }
+            Err(e) => {
+                log::error!(
+                    "Remapping SyncTeX '{}' ... failed: {}",
+                    synctex_path.display(),
+                    e
+                );
+                ReturnCode::RemapError
+            }
+        };
+        std::process::exit(ret_val as i32);
+    }
+
+    if let Some(sm) = m.subcommand_matches("from-latex") {
+        let file_path = Path::new(sm.value_of("file").unwrap());
+        let ret_val = match from_latex_file(file_path) {
+            Ok(path_out) => {
+                log::info!(
+                    "Converting '{}' into '{}' ... ok",
+                    file_path.display(),
+                    path_out.display()
+                );
+                ReturnCode::Ok
+            }
+            Err(e) => {
+                log::error!("Converting '{}' ... failed: {}", file_path.display(), e);
+                ReturnCode::TranspilationError
+            }
+        };
+        std::process::exit(ret_val as i32);
+    }
+
+    let path = Path::new(m.value_of("path").unwrap());
+
+    let write_source_map = m.is_present("source-map");
+    let filters: Vec<String> = m
+        .values_of("filter")
+        .map_or_else(Vec::new, |v| v.map(|f| f.to_string()).collect());
 
     let mut ret_val = ReturnCode::Ok as i32;
 
     let batch: Vec<PathBuf> = if path.is_file() {
         vec![path.to_path_buf()]
     } else if path.is_dir() {
-        match walk_indentex_files(&path) {
-            Ok(b) => b,
+        match walk_indentex_files(path) {
+            // Template files are not transpiled on their own
+            Ok(b) => b
+                .into_iter()
+                .filter(|p| !config.is_template_file(p))
+                .collect(),
             Err(e) => {
                 ret_val = ReturnCode::WalkError as i32;
                 log::error!("{}", e);
@@ -92,16 +557,22 @@ fn main() {
 
     let ret_val_transpilation = batch
         .par_iter()
-        .map(|p| match transpile_file(&p, &options) {
-            Ok(_) => {
-                log::info!("Transpiling file '{}' ... ok", p.display());
-                ReturnCode::Ok
-            }
-            Err(e) => {
-                log::error!("Transpiling file '{}' ... failed: {}", p.display(), e);
-                ReturnCode::TranspilationError
-            }
-        } as i32)
+        .map(
+            |p| match transpile_file(p, &options, write_source_map, &filters) {
+                Ok(_) => {
+                    log::info!("Transpiling file '{}' ... ok", p.display());
+                    ReturnCode::Ok
+                }
+                Err(e @ IndentexError::SuspiciousInput(_)) => {
+                    log::error!("Transpiling file '{}' ... done with {}", p.display(), e);
+                    ReturnCode::SuspiciousInput
+                }
+                Err(e) => {
+                    log::error!("Transpiling file '{}' ... failed: {}", p.display(), e);
+                    ReturnCode::TranspilationError
+                }
+            } as i32,
+        )
         .max()
         .unwrap_or(ReturnCode::Ok as i32);
 
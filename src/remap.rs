@@ -0,0 +1,129 @@
+use indentexlib::source_map::SourceMap;
+use indentexlib::TranspileOptions;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const TRANSPILED_SUFFIX: &str = "_indentex.tex";
+const INDENTEX_SUFFIX: &str = ".inden.tex";
+
+/// Maps lines of transpiled files back to their indentex sources
+///
+/// File names are resolved relative to `base_dir`. Line maps are read from the sidecar if there
+/// is one, otherwise the indentex source is transpiled again. Both are loaded lazily and cached.
+pub struct Remapper<'a> {
+    base_dir: PathBuf,
+    options: &'a TranspileOptions,
+    source_maps: HashMap<String, Option<SourceMap>>,
+}
+
+impl<'a> Remapper<'a> {
+    pub fn new<T: Into<PathBuf>>(base_dir: T, options: &'a TranspileOptions) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+            options,
+            source_maps: HashMap::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn insert<T: Into<String>>(&mut self, transpiled_name: T, source_map: SourceMap) {
+        self.source_maps
+            .insert(transpiled_name.into(), Some(source_map));
+    }
+
+    /// Name of the indentex source of a transpiled file, `None` if it is not a transpiled file
+    pub fn source_name(transpiled_name: &str) -> Option<String> {
+        transpiled_name
+            .strip_suffix(TRANSPILED_SUFFIX)
+            .filter(|stem| !stem.is_empty())
+            .map(|stem| format!("{}{}", stem, INDENTEX_SUFFIX))
+    }
+
+    /// Map a one-based line of a transpiled file to the one-based line of its indentex source
+    pub fn source_line(&mut self, transpiled_name: &str, line: usize) -> Option<usize> {
+        let output_line = line.checked_sub(1)?;
+        self.source_map(transpiled_name)?
+            .source_line(output_line)
+            .map(|l| l + 1)
+    }
+
+    fn source_map(&mut self, transpiled_name: &str) -> Option<&SourceMap> {
+        if !self.source_maps.contains_key(transpiled_name) {
+            let source_map = self.load(transpiled_name);
+            self.source_maps
+                .insert(transpiled_name.to_string(), source_map);
+        }
+        self.source_maps[transpiled_name].as_ref()
+    }
+
+    fn load(&self, transpiled_name: &str) -> Option<SourceMap> {
+        use crate::file_utils::{read_and_trim_lines, source_map_path};
+
+        let source_name = Self::source_name(transpiled_name)?;
+        let sidecar_path = source_map_path(self.base_dir.join(transpiled_name));
+
+        if sidecar_path.is_file() {
+            let source_map = std::fs::read_to_string(&sidecar_path)
+                .ok()
+                .and_then(|s| SourceMap::from_sidecar(&s));
+            if source_map.is_none() {
+                log::warn!("Could not read line map '{}'", sidecar_path.display());
+            }
+            source_map
+        } else {
+            match read_and_trim_lines(self.base_dir.join(&source_name)) {
+                Ok(lines) => Some(indentexlib::transpile_checked(lines, self.options).source_map),
+                Err(e) => {
+                    log::warn!("Could not read indentex source '{}': {}", source_name, e);
+                    None
+                }
+            }
+        }
+    }
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    use super::Remapper;
+
+    #[test]
+    fn source_name() {
+        assert_eq!(
+            Remapper::source_name("./foo_indentex.tex"),
+            Some("./foo.inden.tex".to_string())
+        );
+        assert_eq!(
+            Remapper::source_name("foo.bar_indentex.tex"),
+            Some("foo.bar.inden.tex".to_string())
+        );
+        assert_eq!(Remapper::source_name("_indentex.tex"), None);
+        assert_eq!(Remapper::source_name("foo.tex"), None);
+        assert_eq!(Remapper::source_name("foo.inden.tex"), None);
+    }
+
+    #[test]
+    fn source_line() {
+        use indentexlib::source_map::SourceMap;
+        use indentexlib::TranspileOptions;
+
+        let options = TranspileOptions {
+            prepend_do_not_edit_notice: false,
+            ..Default::default()
+        };
+        let mut remapper = Remapper::new("/nonexistent", &options);
+        remapper.insert(
+            "foo_indentex.tex",
+            SourceMap::from_sidecar("0\n1\n1\n5").unwrap(),
+        );
+
+        assert_eq!(remapper.source_line("foo_indentex.tex", 0), None);
+        assert_eq!(remapper.source_line("foo_indentex.tex", 1), None);
+        assert_eq!(remapper.source_line("foo_indentex.tex", 2), Some(1));
+        assert_eq!(remapper.source_line("foo_indentex.tex", 4), Some(5));
+        assert_eq!(remapper.source_line("foo_indentex.tex", 5), None);
+        assert_eq!(remapper.source_line("bar_indentex.tex", 1), None);
+        assert_eq!(remapper.source_line("bar.tex", 1), None);
+    }
+}
+// LCOV_EXCL_STOP
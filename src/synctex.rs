@@ -0,0 +1,130 @@
+use crate::remap::Remapper;
+use std::collections::HashMap;
+
+const INPUT_PREFIX: &str = "Input:";
+/// Record types which carry a `tag,line` reference to an input file
+const RECORDS_WITH_LINE: &str = "[(vhxkg$";
+
+/// Switch input files and line numbers in a SyncTeX file from transpiled files to their sources
+///
+/// Anchors (`!` records) are left untouched since SyncTeX parsers skip them anyway.
+pub fn remap_synctex(synctex: &str, remapper: &mut Remapper) -> String {
+    // Input tags which refer to transpiled files
+    let mut transpiled_inputs: HashMap<String, String> = HashMap::new();
+    let mut remapped = String::with_capacity(synctex.len());
+
+    for line in synctex.lines() {
+        if let Some(input) = line.strip_prefix(INPUT_PREFIX) {
+            remapped.push_str(&remap_input(input, &mut transpiled_inputs));
+        } else if line.starts_with(|c| RECORDS_WITH_LINE.contains(c)) {
+            remapped.push_str(&remap_record(line, &transpiled_inputs, remapper));
+        } else {
+            remapped.push_str(line);
+        }
+        remapped.push('\n');
+    }
+
+    remapped
+}
+
+/// Rewrite `Input:<tag>:<path>`
+fn remap_input(input: &str, transpiled_inputs: &mut HashMap<String, String>) -> String {
+    let mut parts = input.splitn(2, ':');
+    if let (Some(tag), Some(path)) = (parts.next(), parts.next()) {
+        if let Some(source_name) = Remapper::source_name(path) {
+            transpiled_inputs.insert(tag.to_string(), path.to_string());
+            return format!("{}{}:{}", INPUT_PREFIX, tag, source_name);
+        }
+    }
+    format!("{}{}", INPUT_PREFIX, input)
+}
+
+/// Rewrite `<type><tag>,<line>[,<column>]:<rest>`
+fn remap_record(
+    line: &str,
+    transpiled_inputs: &HashMap<String, String>,
+    remapper: &mut Remapper,
+) -> String {
+    let (record_type, record) = line.split_at(1);
+
+    let tag_len = match record.find(',') {
+        Some(l) => l,
+        None => return line.to_string(),
+    };
+    let (tag, rest) = (&record[..tag_len], &record[tag_len + 1..]);
+    let line_len = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let (line_num, rest) = rest.split_at(line_len);
+
+    let new_line_num = match (transpiled_inputs.get(tag), line_num.parse::<usize>()) {
+        (Some(path), Ok(n)) => remapper.source_line(path, n),
+        _ => None,
+    };
+    match new_line_num {
+        Some(n) => format!("{}{},{}{}", record_type, tag, n, rest),
+        None => line.to_string(),
+    }
+}
+
+// LCOV_EXCL_START
+#[cfg(test)]
+mod tests {
+    use super::remap_synctex;
+    use crate::remap::Remapper;
+    use indentexlib::source_map::SourceMap;
+    use indentexlib::TranspileOptions;
+
+    #[test]
+    fn remap_inputs_and_records() {
+        let options = TranspileOptions {
+            prepend_do_not_edit_notice: false,
+            ..Default::default()
+        };
+        let mut remapper = Remapper::new("/nonexistent", &options);
+        // Output lines 1 to 4 are mapped to source lines -, 7, 8, 7
+        remapper.insert(
+            "/doc/foo_indentex.tex",
+            SourceMap::from_sidecar("0\n7\n8\n7\n").unwrap(),
+        );
+
+        let synctex = "SyncTeX Version:1\n\
+                       Input:1:/doc/main.tex\n\
+                       Input:2:/doc/foo_indentex.tex\n\
+                       Output:pdf\n\
+                       Content:\n\
+                       !120\n\
+                       {1\n\
+                       [1,3:4736286,46092373:30718982,45724715,0\n\
+                       [2,2:4736286,46092373:30718982,45724715,0\n\
+                       h2,3,5:4736286,46092373:30718982,45724715,0\n\
+                       x2,1:4736286,46092373\n\
+                       k2,4:4736286,46092373:-1000\n\
+                       g2,9:4736286,46092373\n\
+                       $2,3:4736286,46092373\n\
+                       ]\n\
+                       }1\n\
+                       Postamble:\n\
+                       Count:8\n";
+        let expected = "SyncTeX Version:1\n\
+                        Input:1:/doc/main.tex\n\
+                        Input:2:/doc/foo.inden.tex\n\
+                        Output:pdf\n\
+                        Content:\n\
+                        !120\n\
+                        {1\n\
+                        [1,3:4736286,46092373:30718982,45724715,0\n\
+                        [2,7:4736286,46092373:30718982,45724715,0\n\
+                        h2,8,5:4736286,46092373:30718982,45724715,0\n\
+                        x2,1:4736286,46092373\n\
+                        k2,7:4736286,46092373:-1000\n\
+                        g2,9:4736286,46092373\n\
+                        $2,8:4736286,46092373\n\
+                        ]\n\
+                        }1\n\
+                        Postamble:\n\
+                        Count:8\n";
+        assert_eq!(remap_synctex(synctex, &mut remapper), expected);
+    }
+}
+// LCOV_EXCL_STOP